@@ -2,7 +2,7 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion, Benchmark
 use criterion_cycles_per_byte::CyclesPerByte;
 
 use pq_newhope::{
-    poly::{Poly, Ntt, FromSeed},
+    poly::{Poly, Ntt, FromSeed, Natural, Small, Time, Shake128X4},
     Pke, Parameter, Cpa, Cca, h,
 };
 use pq_kem::Kem;
@@ -10,15 +10,15 @@ use sha3::Shake256;
 use rac::generic_array::{
     GenericArray,
     sequence::GenericSequence,
-    typenum::{U32, U64, U1024, B0},
+    typenum::{U32, U64},
 };
 
 fn gen_poly(a: &GenericArray<u8, U32>) {
-    let pke = Parameter::<U1024>::new(a);
+    let pke = Parameter::<1024>::new(a);
     black_box(pke);
 }
 
-fn ntt(poly: Poly<U1024, (B0, B0, B0)>) {
+fn ntt(poly: Poly<1024, (Natural, Small, Time)>) {
     black_box(poly.ntt());
 }
 
@@ -28,10 +28,23 @@ fn pke(
     enc: &GenericArray<u8, U32>,
     plain: &GenericArray<u8, U32>,
 ) {
-    let pke = Parameter::<U1024>::new(a);
+    let pke = Parameter::<1024>::new(a);
     let (pk_a, sk_a) = pke.generate(gen);
     let (pk_b, ct) = pke.encrypt(enc, &pk_a, plain);
-    let plain_b = Parameter::<U1024>::decrypt(&pk_b, &sk_a, &ct);
+    let plain_b = Parameter::<1024>::decrypt(&pk_b, &sk_a, &ct);
+    black_box(plain_b);
+}
+
+// the parameter is reused, so it is precomputed beforehand
+fn pke_precomputed(
+    pke: &Parameter<1024>,
+    gen: &GenericArray<u8, U32>,
+    enc: &GenericArray<u8, U32>,
+    plain: &GenericArray<u8, U32>,
+) {
+    let (pk_a, sk_a) = pke.generate(gen);
+    let (pk_b, ct) = pke.encrypt(enc, &pk_a, plain);
+    let plain_b = Parameter::<1024>::decrypt(&pk_b, &sk_a, &ct);
     black_box(plain_b);
 }
 
@@ -53,11 +66,11 @@ where
 }
 
 fn cpa(g: &GenericArray<u8, U32>, e: &GenericArray<u8, U32>) {
-    black_box(kem::<Cpa<U1024>>(g, e));
+    black_box(kem::<Cpa<1024>>(g, e));
 }
 
 fn cca(g: &GenericArray<u8, U64>, e: &GenericArray<u8, U32>) {
-    black_box(kem::<Cca<U1024>>(g, e));
+    black_box(kem::<Cca<1024>>(g, e));
 }
 
 fn bench(c: &mut Criterion<CyclesPerByte>) {
@@ -77,13 +90,18 @@ fn bench(c: &mut Criterion<CyclesPerByte>) {
         GenericArray::generate(|_| rand::random()),
     );
 
-    let p = Poly::<U1024, (B0, B0, B0)>::random(&a.into());
+    let p = Poly::<1024, (Natural, Small, Time)>::random::<Shake128X4>(&a.into());
+    let mut parameter = Parameter::<1024>::new(&a);
+    parameter.precompute();
 
     group.bench_function(BenchmarkId::new("gen", 0), |b| b.iter(|| gen_poly(&a)));
     group.bench_function(BenchmarkId::new("ntt", 0), |b| b.iter(|| ntt(p.clone())));
     group.bench_function(BenchmarkId::new("pke", 0), |b| {
         b.iter(|| pke(&a, &gen, &enc, &plain))
     });
+    group.bench_function(BenchmarkId::new("pke_precomputed", 0), |b| {
+        b.iter(|| pke_precomputed(&parameter, &gen, &enc, &plain))
+    });
     group.bench_function(BenchmarkId::new("cpa", 0), |b| {
         b.iter(|| cpa(&g_cpa, &e_cpa))
     });
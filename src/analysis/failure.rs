@@ -0,0 +1,246 @@
+// A heuristic estimate of the probability that the decryption fails. The coefficients
+// the decryption gives are the negated message plus the noise
+// `s_a * e_1 - e_a * s_b - e_2 - e_c`, where `e_c` is the error of the compression
+// of the cipher text. A coefficient of the products is the sum of `2 * N` products
+// of distinct noise coefficients, so its distribution is the convolution of theirs.
+// But the `N / 256` coefficients a bit is decoded from share the same secrets,
+// the estimate takes them independent, so it is not the failure probability
+// of the specification, which states the bounds `2 ^ -213` and `2 ^ -216`.
+
+use std::{vec, vec::Vec};
+use crate::poly::{Coefficient, Modulus, ParameterSet, Ring, PolySize, threshold};
+use rac::generic_array::typenum::Unsigned;
+
+// `2 ^ -500`, the probabilities less than it are dropped from the ends of the
+// distributions, so the products of two of them do not get subnormal
+const CUT: f64 = 3.054936363499605e-151;
+
+/// A heuristic estimate of the base 2 logarithm of the probability that
+/// the decryption of the ring of size `N` does not give the message back.
+///
+/// The distribution of a coefficient is convolved from the noise, the second
+/// error and the error of the compression, the cipher text is taken uniform for
+/// the latter. Each bit of the message is decoded from `N / 256` coefficients,
+/// they are correlated through the shared secrets, but taken independent here,
+/// and the failures of the 256 bits are joined by the union bound. So it is
+/// an estimate, not a bound, and it is far below the bounds of the specification.
+/// It is the negative infinity if the probability is less than about `2 ^ -500`.
+pub fn failure_heuristic<const N: usize, P>() -> f64
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    let error = error::<N, P>();
+
+    // the bit is set if the sum is less than the threshold
+    let threshold = threshold::<N, P::Modulus>() as i64;
+    let one = sums::<N, P>(&error, 1).above(threshold);
+    let zero = sums::<N, P>(&error, 0).below(threshold);
+
+    (256.0 * one.max(zero)).min(1.0).log2()
+}
+
+// the distribution of a coefficient the decryption gives, but the message
+pub(super) fn error<const N: usize, P>() -> Law
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    let noise = noise::<P>();
+    let compression = compression::<P::Modulus>(P::CompressionBits::U32);
+    noise
+        .product(&noise)
+        .power(2 * N)
+        .add(&noise.map(|x| -x))
+        .add(&compression.map(|x| -x))
+}
+
+// the distribution of the sum of the distances of the coefficients of the bit
+pub(super) fn sums<const N: usize, P>(error: &Law, bit: u8) -> Law
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    let q = P::Modulus::Q as i64;
+    let m = (Coefficient::<P::Modulus>::MIDDLE.data() as i64) * (bit as i64);
+    error
+        .map(|x| {
+            let c = Coefficient::<P::Modulus>::new((x - m).rem_euclid(q) as u16);
+            c.flip_abs() as i64
+        })
+        .power(N / 256)
+}
+
+// the distribution of a coefficient of the noise
+fn noise<P>() -> Law
+where
+    P: ParameterSet,
+{
+    Law::new(-(P::NOISE.bound() as i64), P::NOISE.masses())
+}
+
+// the distribution of `decompress(compress(x)) - x` for the uniform `x`
+fn compression<M>(bits: u32) -> Law
+where
+    M: Modulus,
+{
+    let q = M::Q as i64;
+    Law::from_pairs((0..q).map(|x| {
+        let c = Coefficient::<M>::new(x as u16).compress(bits);
+        let y = Coefficient::<M>::decompress(c, bits).freeze() as i64;
+        // the representative of `y - x` from `-q / 2` to `q / 2`
+        let e = (y - x).rem_euclid(q);
+        (if e > q / 2 { e - q } else { e }, 1.0 / (q as f64))
+    }))
+}
+
+// the distribution over the integers from `offset`
+#[derive(Clone, Debug)]
+pub(super) struct Law {
+    offset: i64,
+    masses: Vec<f64>,
+}
+
+impl Law {
+    fn new(offset: i64, masses: Vec<f64>) -> Self {
+        let start = masses.iter().position(|&m| m >= CUT).unwrap_or(0);
+        let end = masses.iter().rposition(|&m| m >= CUT).map_or(0, |e| e + 1);
+        Law {
+            offset: offset + (start as i64),
+            masses: masses[start..end].to_vec(),
+        }
+    }
+
+    fn from_pairs<I>(pairs: I) -> Self
+    where
+        I: Iterator<Item = (i64, f64)> + Clone,
+    {
+        let min = pairs.clone().map(|(x, _)| x).min().unwrap_or(0);
+        let max = pairs.clone().map(|(x, _)| x).max().unwrap_or(0);
+        let mut masses = vec![0.0; (max - min + 1) as usize];
+        for (x, m) in pairs {
+            masses[(x - min) as usize] += m;
+        }
+        Law::new(min, masses)
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (i64, f64)> + Clone + '_ {
+        let offset = self.offset;
+        self.masses
+            .iter()
+            .enumerate()
+            .map(move |(i, &m)| (offset + (i as i64), m))
+    }
+
+    // the distribution of `f(x)`
+    fn map<F>(&self, f: F) -> Self
+    where
+        F: Fn(i64) -> i64 + Clone,
+    {
+        Law::from_pairs(self.pairs().map(move |(x, m)| (f(x), m)))
+    }
+
+    // the distribution of the product of the independent variables
+    fn product(&self, other: &Self) -> Self {
+        Law::from_pairs(
+            self.pairs()
+                .flat_map(|(x, a)| other.pairs().map(move |(y, b)| (x * y, a * b))),
+        )
+    }
+
+    // the distribution of the sum of the independent variables
+    fn add(&self, other: &Self) -> Self {
+        let mut masses = vec![0.0; self.masses.len() + other.masses.len() - 1];
+        for (i, a) in self.masses.iter().enumerate() {
+            for (m, b) in masses[i..].iter_mut().zip(other.masses.iter()) {
+                *m += a * b;
+            }
+        }
+        Law::new(self.offset + other.offset, masses)
+    }
+
+    // the distribution of the sum of `n` independent copies
+    fn power(&self, mut n: usize) -> Self {
+        let (mut r, mut x) = (Law::new(0, vec![1.0]), self.clone());
+        while n > 0 {
+            if n & 1 == 1 {
+                r = r.add(&x);
+            }
+            n >>= 1;
+            if n > 0 {
+                x = x.add(&x);
+            }
+        }
+        r
+    }
+
+    // `P(x >= t)`
+    fn above(&self, t: i64) -> f64 {
+        self.pairs().filter(|&(x, _)| x >= t).map(|(_, m)| m).sum()
+    }
+
+    // `P(x < t)`
+    fn below(&self, t: i64) -> f64 {
+        self.pairs().filter(|&(x, _)| x < t).map(|(_, m)| m).sum()
+    }
+
+    #[cfg(test)]
+    pub(super) fn mean(&self) -> f64 {
+        self.pairs().map(|(x, m)| m * (x as f64)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Law, failure_heuristic, noise, compression};
+    use crate::poly::{Distribution, NewHope, Q12289, Gaussian};
+
+    #[test]
+    fn convolution() {
+        // the sum of two binomials of `k` is the binomial of `2 * k`
+        let law = Law::new(-4, Distribution::Binomial(4).masses()).power(2);
+        let expected = Distribution::Binomial(8).masses();
+        assert_eq!(law.offset, -8);
+        for (a, b) in law.masses.iter().zip(expected.iter()) {
+            assert!((a - b).abs() < 1e-15);
+        }
+
+        // the variance of the product is the square of the variance
+        let noise = noise::<NewHope>();
+        let variance = noise
+            .product(&noise)
+            .pairs()
+            .map(|(x, m)| m * (x * x) as f64)
+            .sum::<f64>();
+        assert!((variance - 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compression_error() {
+        let law = compression::<Q12289>(3);
+        let total = law.masses.iter().sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-12);
+        // `q / 16` rounded
+        assert!(law.offset >= -769 && law.offset + (law.masses.len() as i64) <= 769);
+        assert!(law.mean().abs() < 1.0);
+    }
+
+    #[test]
+    fn newhope() {
+        // the specification bounds them by `2 ^ -213` and `2 ^ -216`
+        let p_512 = failure_heuristic::<512, NewHope>();
+        let p_1024 = failure_heuristic::<1024, NewHope>();
+        assert!(p_512 <= -213.0, "{}", p_512);
+        assert!(p_1024 <= -216.0, "{}", p_1024);
+        // the larger ring decodes each bit from more coefficients
+        assert!(p_1024 < p_512, "{} {}", p_1024, p_512);
+
+        // the Gaussian of the same variance has the heavier tail
+        let p_gaussian = failure_heuristic::<512, Gaussian>();
+        assert!(
+            p_gaussian > p_512 && p_gaussian < p_512 + 10.0,
+            "{}",
+            p_gaussian
+        );
+    }
+}
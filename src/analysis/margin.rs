@@ -0,0 +1,176 @@
+// The empirical margins of the decryption. The harness encrypts random messages to
+// random keys and counts how far the sum of each bit is from the threshold, the
+// failures are far too rare to observe, but the histogram shows the drift of the
+// implementation from the distribution `failure_heuristic` estimates from.
+
+use core::{fmt, num::NonZeroU32};
+use std::{vec, vec::Vec};
+use crate::{
+    Pke, Parameter, Symmetric,
+    poly::{ParameterSet, Ring, PolySize, threshold},
+};
+use rac::generic_array::GenericArray;
+
+/// The counts of the margins of the bits of the decrypted messages. The margin
+/// is the distance of the sum of the bit to the threshold toward the failure,
+/// the negative one is the failure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// The width of the bucket.
+    pub width: NonZeroU32,
+    /// The bucket `i` counts the margins from `i * width` to `(i + 1) * width`.
+    pub buckets: Vec<u64>,
+    /// The number of the bits that failed.
+    pub failures: u64,
+    /// The least margin.
+    pub least: i64,
+}
+
+impl Histogram {
+    fn new(width: NonZeroU32, threshold: u32) -> Self {
+        Histogram {
+            width,
+            buckets: vec![0; (threshold / width.get() + 1) as usize],
+            failures: 0,
+            least: i64::MAX,
+        }
+    }
+
+    fn add(&mut self, margin: i64) {
+        if margin < 0 {
+            self.failures += 1;
+        } else {
+            self.buckets[(margin / (self.width.get() as i64)) as usize] += 1;
+        }
+        self.least = self.least.min(margin);
+    }
+
+    /// The number of the bits counted.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum::<u64>() + self.failures
+    }
+
+    /// The mean of the margins, the failures are not counted, so it is `None`
+    /// if every bit failed.
+    pub fn mean(&self) -> Option<f64> {
+        let samples = self.count() - self.failures;
+        if samples == 0 {
+            return None;
+        }
+        let total = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| ((i as f64) + 0.5) * (self.width.get() as f64) * (c as f64))
+            .sum::<f64>();
+        Some(total / (samples as f64))
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "failures: {}, least margin: {}",
+            self.failures, self.least
+        )?;
+        for (i, c) in self.buckets.iter().enumerate().filter(|(_, &c)| c != 0) {
+            let from = (i as u32) * self.width.get();
+            writeln!(f, "{:>6}..{:<6} {}", from, from + self.width.get(), c)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decrypts `count` random messages encrypted to random keys by random public
+/// parameters and counts the margins of their bits, `random` fills the seeds.
+pub fn histogram<const N: usize, S, P, R>(
+    count: usize,
+    width: NonZeroU32,
+    mut random: R,
+) -> Histogram
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+    R: FnMut(&mut [u8]),
+{
+    let mut seed = || {
+        let mut seed = GenericArray::default();
+        random(seed.as_mut());
+        seed
+    };
+
+    let threshold = threshold::<N, P::Modulus>();
+    let mut histogram = Histogram::new(width, threshold);
+    for _ in 0..count {
+        let parameter = Parameter::<N, S, P>::new(&seed());
+        let (pk_a, sk_a) = parameter.generate(&seed());
+        let plain = seed();
+        let (pk_b, ct) = parameter.encrypt(&seed(), &pk_a, &plain);
+        let (_, margin) = Parameter::<N, S, P>::decrypt_with_margin(&pk_b, &sk_a, &ct);
+
+        let threshold = threshold as i64;
+        for (l, &t) in margin.sums.iter().enumerate() {
+            // the set bit needs the sum less than the threshold, the clear one does not
+            let margin = if (plain[l / 8] >> (l % 8)) & 1 == 1 {
+                threshold - (t as i64) - 1
+            } else {
+                (t as i64) - threshold
+            };
+            histogram.add(margin);
+        }
+    }
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Histogram, histogram};
+    use core::num::NonZeroU32;
+    use crate::{
+        Keccak,
+        analysis::failure::{error, sums},
+        poly::{NewHope, Q12289, threshold},
+    };
+
+    #[test]
+    fn newhope() {
+        let width = NonZeroU32::new(256).unwrap();
+        let h = histogram::<512, Keccak, NewHope, _>(32, width, |seed| {
+            seed.iter_mut().for_each(|b| *b = rand::random())
+        });
+        assert_eq!(h.count(), 32 * 256);
+        assert_eq!(h.failures, 0);
+        assert!(h.least > 0);
+
+        // the mean margin of the uniform bits is the one of the analysis
+        let error = error::<512, NewHope>();
+        let threshold = threshold::<512, Q12289>() as f64;
+        let one = threshold - sums::<512, NewHope>(&error, 1).mean() - 1.0;
+        let zero = sums::<512, NewHope>(&error, 0).mean() - threshold;
+        let expected = (one + zero) / 2.0;
+        let mean = h.mean().unwrap();
+        assert!(
+            (mean / expected - 1.0).abs() < 0.02,
+            "{} {}",
+            mean,
+            expected
+        );
+    }
+
+    #[test]
+    fn failed() {
+        let mut h = Histogram::new(NonZeroU32::new(256).unwrap(), 12289);
+        assert_eq!(h.mean(), None);
+        h.add(-1);
+        h.add(-300);
+        assert_eq!(h.count(), 2);
+        assert_eq!(h.failures, 2);
+        assert_eq!(h.least, -300);
+        assert_eq!(h.mean(), None);
+
+        h.add(10);
+        assert_eq!(h.mean(), Some(128.0));
+    }
+}
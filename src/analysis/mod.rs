@@ -0,0 +1,88 @@
+// The analysis of the parameter sets. It is computed at runtime with floating
+// point, so it needs `std`, and it does not touch the keys.
+
+mod failure;
+mod security;
+mod margin;
+
+pub use self::failure::failure_heuristic;
+pub use self::margin::{Histogram, histogram};
+pub use self::security::{Model, Attack, core_svp, primal, dual};
+
+use core::fmt;
+use crate::poly::{Distribution, ParameterSet, Ring, PolySize};
+
+/// The failure rate and the hardness of the ring of size `N`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Report {
+    /// The heuristic estimate of the base 2 logarithm of the probability
+    /// of the decryption failure, see `failure_heuristic`.
+    pub failure_heuristic: f64,
+    pub classical: Attack,
+    pub quantum: Attack,
+}
+
+impl Report {
+    pub fn new<const N: usize, P>() -> Self
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        Report {
+            failure_heuristic: failure_heuristic::<N, P>(),
+            classical: core_svp::<N, P>(Model::Classical),
+            quantum: core_svp::<N, P>(Model::Quantum),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failure heuristic 2^{:.1}, classical 2^{:.0} (b = {}), quantum 2^{:.0} (b = {})",
+            self.failure_heuristic,
+            self.classical.cost.floor(),
+            self.classical.block_size,
+            self.quantum.cost.floor(),
+            self.quantum.block_size,
+        )
+    }
+}
+
+// the deviation of the noise the sampler gives
+fn deviation(d: Distribution) -> f64 {
+    let bound = d.bound() as f64;
+    let masses = d.masses();
+    let variance = masses
+        .iter()
+        .enumerate()
+        .map(|(i, m)| m * (i as f64 - bound) * (i as f64 - bound))
+        .sum::<f64>();
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Report, deviation};
+    use crate::poly::{Distribution, NewHope};
+    use std::{format, string::ToString};
+
+    #[test]
+    fn report() {
+        assert_eq!(deviation(Distribution::Binomial(8)), 2.0);
+        let report = Report::new::<512, NewHope>();
+        assert!(
+            report.failure_heuristic <= -213.0,
+            "{}",
+            report.failure_heuristic
+        );
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "failure heuristic 2^{:.1}, classical 2^112 (b = 385), quantum 2^102 (b = 385)",
+                report.failure_heuristic,
+            ),
+        );
+    }
+}
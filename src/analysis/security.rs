@@ -0,0 +1,162 @@
+// The hardness of the ring learning with errors by the core SVP methodology of
+// NewHope. The attacks are the primal and the dual ones by BKZ of the block size `b`,
+// and their cost is the one call of the sieve in the dimension `b`, `2 ^ (0.292 * b)`
+// classically and `2 ^ (0.265 * b)` on a quantum computer. The attacker has at most
+// `N` samples, the ones of the public key, the search over them is exhaustive.
+
+use core::f64::consts::{PI, E};
+use crate::poly::{Modulus, ParameterSet, Ring, PolySize};
+
+/// The computer of the attacker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    Classical,
+    Quantum,
+}
+
+impl Model {
+    // the base 2 logarithm of the cost of the sieve in the dimension `b`
+    fn sieve(self, b: usize) -> f64 {
+        match self {
+            Model::Classical => 0.292 * (b as f64),
+            Model::Quantum => 0.265 * (b as f64),
+        }
+    }
+}
+
+/// The cheapest parameters of an attack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attack {
+    /// The number of the samples used.
+    pub samples: usize,
+    /// The block size of BKZ.
+    pub block_size: usize,
+    /// The base 2 logarithm of the cost.
+    pub cost: f64,
+}
+
+/// The hardness of the ring of size `N`, the cheaper of the primal and the dual
+/// attacks, the noise width is the deviation of the noise of `P`.
+pub fn core_svp<const N: usize, P>(model: Model) -> Attack
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    let deviation = super::deviation(P::NOISE);
+    let primal = primal(N, P::Modulus::Q, deviation, model);
+    let dual = dual(N, P::Modulus::Q, deviation, model);
+    if dual.cost < primal.cost {
+        dual
+    } else {
+        primal
+    }
+}
+
+/// The primal attack, BKZ finds the short vector of the secret and the error
+/// embedded into the lattice of the samples.
+pub fn primal(n: usize, q: u16, deviation: f64, model: Model) -> Attack {
+    let q = q as f64;
+    let mut best = Attack::none();
+    for m in 1..=n {
+        let d = (n + m) as f64;
+        // the smallest block size that succeeds
+        let b = (50..=(n + m)).find(|&b| {
+            let delta = delta(b);
+            let b = b as f64;
+            deviation * b.sqrt() < delta.powf(2.0 * b - d - 1.0) * q.powf((m as f64) / d)
+        });
+        if let Some(b) = b {
+            best = best.min(m, b, model.sieve(b));
+        }
+    }
+    best
+}
+
+/// The dual attack, BKZ finds the short vector of the dual lattice, it
+/// distinguishes the samples with the advantage `eps`, repeated `1 / eps ^ 2`
+/// times, and the sieve gives many short vectors at once.
+pub fn dual(n: usize, q: u16, deviation: f64, model: Model) -> Attack {
+    let q = q as f64;
+    let mut best = Attack::none();
+    for m in 1..=n {
+        let d = (n + m) as f64;
+        for b in 50..=(n + m) {
+            let length = delta(b).powf(d) * q.powf((n as f64) / d);
+            let tau = length * deviation / q;
+            let log_eps = -2.0 * PI * PI * tau * tau / 2.0f64.ln();
+            // the sieve gives `(4 / 3) ^ (b / 2)` vectors
+            let vectors = (b as f64) * (4.0f64 / 3.0).sqrt().log2();
+            let repeat = (-2.0 * log_eps - vectors).max(0.0);
+            best = best.min(m, b, model.sieve(b) + repeat);
+            // the larger block size only costs more
+            if repeat == 0.0 {
+                break;
+            }
+        }
+    }
+    best
+}
+
+// the root Hermite factor of BKZ of the block size `b`
+fn delta(b: usize) -> f64 {
+    let b = b as f64;
+    ((PI * b).powf(1.0 / b) * b / (2.0 * PI * E)).powf(1.0 / (2.0 * b - 2.0))
+}
+
+impl Attack {
+    fn none() -> Self {
+        Attack {
+            samples: 0,
+            block_size: 0,
+            cost: f64::INFINITY,
+        }
+    }
+
+    fn min(self, samples: usize, block_size: usize, cost: f64) -> Self {
+        if cost < self.cost {
+            Attack {
+                samples,
+                block_size,
+                cost,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Model, core_svp, primal, dual};
+    use crate::poly::{NewHope, Gaussian};
+
+    #[test]
+    fn newhope() {
+        // the specification gives 112 and 101 bits for NewHope-512, 257 and 233
+        // bits for NewHope-1024, the searches differ by a bit
+        let expected = [(512, 112.0, 101.0), (1024, 257.0, 233.0)];
+        for (n, classical, quantum) in expected {
+            let c = primal(n, 12289, 2.0, Model::Classical)
+                .cost
+                .min(dual(n, 12289, 2.0, Model::Classical).cost);
+            let q = primal(n, 12289, 2.0, Model::Quantum)
+                .cost
+                .min(dual(n, 12289, 2.0, Model::Quantum).cost);
+            assert!((c.floor() - classical).abs() <= 1.0, "{}", c);
+            assert!((q.floor() - quantum).abs() <= 1.0, "{}", q);
+        }
+
+        assert_eq!(primal(512, 12289, 2.0, Model::Classical).block_size, 386);
+        assert_eq!(primal(1024, 12289, 2.0, Model::Classical).block_size, 886);
+        assert_eq!(dual(512, 12289, 2.0, Model::Classical).block_size, 385);
+        assert_eq!(dual(1024, 12289, 2.0, Model::Classical).block_size, 881);
+
+        let a = core_svp::<512, NewHope>(Model::Classical);
+        assert_eq!((a.samples, a.block_size), (502, 385));
+        assert_eq!(a.cost.floor(), 112.0);
+
+        // the deviation of the Gaussian is 2 as well
+        let b = core_svp::<512, Gaussian>(Model::Classical);
+        assert!((a.cost - b.cost).abs() < 1.0);
+    }
+}
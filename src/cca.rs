@@ -1,9 +1,9 @@
 use super::{
-    hash,
-    poly::PolySize,
+    Error, KemExt, Symmetric, Keccak, hash,
+    poly::{Ring, PolySize, ParameterSet, NewHope},
     pke::{Pke, PublicKey, SecretKey, Parameter},
 };
-use core::marker::PhantomData;
+use core::{fmt, marker::PhantomData};
 use rac::{
     LineValid, Line, Concat,
     generic_array::{
@@ -11,65 +11,251 @@ use rac::{
         typenum::{U32, U64},
     },
 };
-use sha3::digest::{Update, ExtendableOutput};
+use sha3::digest::{Update, ExtendableOutput, XofReader};
 use pq_kem::Kem;
+#[cfg(feature = "low-memory")]
+use super::pke::stream;
 
-pub struct Cca<N>(PhantomData<N>)
+/// The cca variant, the symmetric primitives are `S`, the parameter set is `P`.
+pub struct Cca<const N: usize, S = Keccak, P = NewHope>(PhantomData<(S, P)>)
 where
-    N: PolySize;
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric;
 
 #[derive(Clone)]
-pub struct PublicKeyCca<N>
+pub struct PublicKeyCca<const N: usize, S = Keccak, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
-    pk: PublicKey<N>,
-    parameter: Parameter<N>,
+    pk: PublicKey<N, P>,
+    // kept once expanded by `expand`, otherwise expanded for each use
+    parameter: Option<Parameter<N, S, P>>,
     seed: GenericArray<u8, U32>,
+    pk_hash: GenericArray<u8, U32>,
 }
 
-#[derive(Clone)]
-pub struct SecretKeyCca<N>
+impl<const N: usize, S, P> PublicKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    fn new(
+        pk: PublicKey<N, P>,
+        parameter: Option<Parameter<N, S, P>>,
+        seed: GenericArray<u8, U32>,
+    ) -> Self {
+        // `h::<S::Hash, _, _>(&public_key)`, the `Kem` hash is `S::Hash` as well
+        let mut pk_hash = GenericArray::default();
+        S::Hash::default()
+            .chain(pk.clone_line())
+            .chain(seed)
+            .finalize_xof()
+            .read(pk_hash.as_mut());
+        PublicKeyCca {
+            pk,
+            parameter,
+            seed,
+            pk_hash,
+        }
+    }
+
+    /// The hash of the encoded public key, `h::<S::Hash, _, _>(&public_key)`.
+    pub fn hash(&self) -> &GenericArray<u8, U32> {
+        &self.pk_hash
+    }
+}
+
+pub struct SecretKeyCca<const N: usize, S = Keccak, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
-    sk: SecretKey<N>,
+    sk: SecretKey<N, P>,
+    pk: PublicKeyCca<N, S, P>,
     reject: GenericArray<u8, U32>,
-    pk: PublicKeyCca<N>,
+}
+
+impl<const N: usize, S, P> fmt::Debug for SecretKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKeyCca(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> zeroize::Zeroize for SecretKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    fn zeroize(&mut self) {
+        self.sk.zeroize();
+        hash::scrub(self.reject.as_mut());
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> Drop for SecretKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    fn drop(&mut self) {
+        hash::scrub(self.reject.as_mut());
+    }
 }
 
 #[derive(Clone)]
-pub struct CipherTextCca<N>
+pub struct CipherTextCca<const N: usize, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
 {
-    pk: PublicKey<N>,
-    ct: GenericArray<u8, N::CompressedLength>,
+    pk: PublicKey<N, P>,
+    ct: GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
     check: GenericArray<u8, U32>,
 }
 
+impl<const N: usize, S, P> PublicKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    /// Expands the public parameter from the seed and keeps it, the key that is
+    /// not expanded expands the parameter for each encapsulation, and decoding
+    /// does not expand it.
+    pub fn expand(&mut self) {
+        if self.parameter.is_none() {
+            self.parameter = Some(Parameter::new(&self.seed));
+        }
+    }
+
+    /// The public parameter if it is expanded.
+    pub fn parameter(&self) -> Option<&Parameter<N, S, P>> {
+        self.parameter.as_ref()
+    }
+
+    /// Whether the public parameter is already expanded.
+    pub fn is_expanded(&self) -> bool {
+        self.parameter.is_some()
+    }
+
+    /// Expands and precomputes the public parameter, it is worth it
+    /// if the key encapsulates many times, see `Parameter::precompute`.
+    pub fn precompute(&mut self) {
+        self.expand();
+        if let Some(parameter) = &mut self.parameter {
+            parameter.precompute();
+        }
+    }
+
+    /// Whether the public parameter is precomputed.
+    pub fn is_precomputed(&self) -> bool {
+        self.parameter
+            .as_ref()
+            .is_some_and(Parameter::is_precomputed)
+    }
+
+    // the kept parameter, or the one expanded for this use only
+    fn with_parameter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Parameter<N, S, P>) -> R,
+    {
+        match &self.parameter {
+            Some(parameter) => f(parameter),
+            None => f(&Parameter::new(&self.seed)),
+        }
+    }
+
+    /// Checks that the parameter is expanded from the seed, does not expand it.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.pk.validate()?;
+        match &self.parameter {
+            Some(parameter) => parameter.validate(&self.seed),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<const N: usize, S, P> SecretKeyCca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    /// The embedded public key.
+    pub fn public_key(&self) -> &PublicKeyCca<N, S, P> {
+        &self.pk
+    }
+
+    /// Precomputes the secret and the parameter of the embedded public key,
+    /// the decapsulation uses both, it is worth it if the key decapsulates many times.
+    pub fn precompute(&mut self) {
+        self.sk.precompute();
+        self.pk.precompute();
+    }
+
+    /// Whether the secret and the parameter are precomputed.
+    pub fn is_precomputed(&self) -> bool {
+        self.sk.is_precomputed() && self.pk.is_precomputed()
+    }
+
+    /// Checks that the secret is a noise polynomial and the embedded public key
+    /// corresponds to it, the decoding checks only the embedded hash.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.sk.validate()?;
+        self.pk.validate()?;
+        self.pk
+            .with_parameter(|parameter| self.pk.pk.validate_pair(parameter, &self.sk))
+            .map_err(|_| Error::InconsistentPublicKey)
+    }
+
+    /// Checks the secret key and that it embeds exactly this public key.
+    pub fn validate_pair(&self, public_key: &PublicKeyCca<N, S, P>) -> Result<(), Error> {
+        self.validate()?;
+        if public_key.clone_line() == self.pk.clone_line() {
+            Ok(())
+        } else {
+            Err(Error::KeyMismatch)
+        }
+    }
+}
+
+impl<const N: usize, P> CipherTextCca<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// Checks that every coefficient of the cipher text is reduced.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.pk.validate()
+    }
+}
+
 type B = Concat<Concat<GenericArray<u8, U32>, GenericArray<u8, U32>>, GenericArray<u8, U32>>;
 
-impl<N, D> Kem<D> for Cca<N>
+// the public key is hashed with `S::Hash`, so it is the only hash of the `Kem`
+impl<const N: usize, S, P> Kem<S::Hash> for Cca<N, S, P>
 where
-    D: Default + Update + ExtendableOutput,
-    N: PolySize,
-    PublicKeyCca<N>: Clone + LineValid,
-    SecretKeyCca<N>: LineValid,
-    CipherTextCca<N>: Clone + LineValid,
-    Concat<GenericArray<u8, U32>, CipherTextCca<N>>: LineValid,
-    Parameter<N>: Pke<
-        Seed = U32,
-        GenerationSeed = U32,
-        Plain = U32,
-        Cipher = N::CompressedLength,
-        PublicKey = PublicKey<N>,
-        SecretKey = SecretKey<N>,
-    >,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
-    type PublicKey = PublicKeyCca<N>;
-    type SecretKey = SecretKeyCca<N>;
-    type CipherText = CipherTextCca<N>;
+    type PublicKey = PublicKeyCca<N, S, P>;
+    type SecretKey = SecretKeyCca<N, S, P>;
+    type CipherText = CipherTextCca<N, P>;
     type PairSeedLength = U64;
     type PublicKeyHashLength = U32;
     type EncapsulationSeedLength = U32;
@@ -78,21 +264,27 @@ where
     fn generate_pair(
         seed: &GenericArray<u8, Self::PairSeedLength>,
     ) -> (Self::PublicKey, Self::SecretKey) {
-        let Concat(reject, cpa_seed) = Concat::<_, GenericArray<u8, U32>>::clone_array(seed);
-        let Concat(parameter_seed, pk_seed) = hash::h::<D, _, _>(&Concat(hash::B(1), cpa_seed));
-        let parameter = Parameter::new(&parameter_seed);
-        let (pk, sk) = parameter.generate(&pk_seed);
-        let public_key = PublicKeyCca {
-            pk: pk,
-            parameter: parameter,
-            seed: parameter_seed,
+        let Concat(cpa_seed, reject) = Concat::<GenericArray<u8, U32>, _>::clone_array(seed);
+        let Concat(parameter_seed, pk_seed) =
+            hash::h::<S::Hash, _, _>(&Concat(hash::B(1), cpa_seed));
+        #[cfg(not(feature = "low-memory"))]
+        let (pk, sk, parameter) = {
+            let parameter = Parameter::new(&parameter_seed);
+            let (pk, sk) = parameter.generate(&pk_seed);
+            (pk, sk, Some(parameter))
+        };
+        #[cfg(feature = "low-memory")]
+        let (pk, sk, parameter) = {
+            let (pk, sk) = stream::generate::<N, S, P>(&parameter_seed, &pk_seed);
+            (pk, sk, None)
         };
+        let public_key = PublicKeyCca::new(pk, parameter, parameter_seed);
         (
             public_key.clone(),
             SecretKeyCca {
                 sk: sk,
+                pk: public_key,
                 reject: reject,
-                pk: public_key.clone(),
             },
         )
     }
@@ -102,18 +294,32 @@ where
         public_key: &Self::PublicKey,
         public_key_hash: &GenericArray<u8, Self::PublicKeyHashLength>,
     ) -> (Self::CipherText, GenericArray<u8, Self::SharedSecretLength>) {
-        let message: GenericArray<u8, U32> = hash::h::<D, _, _>(&Concat(hash::B(0x04), seed.clone()));
-        let Concat(Concat(b0, b1), b2) = hash::h::<D, Concat<_, GenericArray<u8, U32>>, B>(&Concat(
-            Concat(hash::B(0x08), message.clone()),
-            public_key_hash.clone(),
-        ));
-        let (pk_b, ct) = public_key.parameter.encrypt(&b1, &public_key.pk, &message);
+        // the public key carries its hash, the one of the honest caller is the same
+        let _ = public_key_hash;
+        let mut message: GenericArray<u8, U32> =
+            hash::h::<S::Hash, _, _>(&Concat(hash::B(0x04), *seed));
+        let pk_hash = public_key.pk_hash;
+        let mut input = Concat(Concat(hash::B(0x08), message), pk_hash);
+        let Concat(Concat(b0, mut b1), b2) = hash::h::<S::Hash, _, B>(&input);
+        #[cfg(not(feature = "low-memory"))]
+        let (pk_b, ct) =
+            public_key.with_parameter(|parameter| parameter.encrypt(&b1, &public_key.pk, &message));
+        #[cfg(feature = "low-memory")]
+        let (pk_b, ct) =
+            stream::encrypt::<N, S, P>(&public_key.seed, &b1, &public_key.pk, &message);
         let cipher_text = CipherTextCca {
             pk: pk_b,
             ct: ct,
             check: b2,
         };
-        let shared_secret = hash::h::<D, _, _>(&Concat(b0, cipher_text.clone()));
+        let cipher_text_hash: GenericArray<u8, U32> = hash::h::<S::Hash, _, _>(&cipher_text);
+        let mut key = Concat(b0, cipher_text_hash);
+        let shared_secret = hash::h::<S::Hash, _, _>(&key);
+
+        hash::scrub(message.as_mut());
+        hash::scrub((input.0).1.as_mut());
+        hash::scrub(b1.as_mut());
+        hash::scrub(key.0.as_mut());
         (cipher_text, shared_secret)
     }
 
@@ -122,6 +328,9 @@ where
         public_key_hash: &GenericArray<u8, Self::PublicKeyHashLength>,
         cipher_text: &Self::CipherText,
     ) -> GenericArray<u8, Self::SharedSecretLength> {
+        // the secret key carries the hash, the argument is not trusted
+        let _ = public_key_hash;
+
         #[inline(never)]
         fn c_cmp(a: &[u8], b: &[u8]) -> u8 {
             (0..a.len()).fold(0, |r, i| r | (a[i] ^ b[i]))
@@ -134,123 +343,217 @@ where
             }
         }
 
-        let message: GenericArray<u8, U32> =
-            Parameter::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
-        let Concat(Concat(mut b0, b1), b2) = hash::h::<D, Concat<_, GenericArray<u8, U32>>, B>(
-            &Concat(Concat(hash::B(0x08), message.clone()), public_key_hash.clone()),
-        );
-
-        let (pk_b_cmp, ct_cmp) = secret_key
-            .pk
-            .parameter
-            .encrypt(&b1, &secret_key.pk.pk, &message);
-        let cipher_text_cmp = CipherTextCca {
-            pk: pk_b_cmp,
-            ct: ct_cmp,
-            check: b2,
+        #[cfg(not(feature = "low-memory"))]
+        let mut message: GenericArray<u8, U32> =
+            Parameter::<N, S, P>::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
+        #[cfg(feature = "low-memory")]
+        let mut message = stream::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
+        let pk_hash = secret_key.pk.pk_hash;
+        let mut input = Concat(Concat(hash::B(0x08), message), pk_hash);
+        let Concat(Concat(b0, mut b1), b2) = hash::h::<S::Hash, _, B>(&input);
+
+        #[cfg(not(feature = "low-memory"))]
+        let (fail, cipher_text_hash) = {
+            let pk = &secret_key.pk;
+            let (pk_b_cmp, ct_cmp) =
+                pk.with_parameter(|parameter| parameter.encrypt(&b1, &pk.pk, &message));
+            let mut cipher_text_cmp = CipherTextCca {
+                pk: pk_b_cmp,
+                ct: ct_cmp,
+                check: b2,
+            };
+            let cipher_text_bytes = cipher_text.clone_line();
+            let mut cipher_text_cmp_bytes = cipher_text_cmp.clone_line();
+            let fail = c_cmp(cipher_text_bytes.as_ref(), cipher_text_cmp_bytes.as_ref());
+            hash::scrub(cipher_text_cmp.ct.as_mut());
+            hash::scrub(cipher_text_cmp.check.as_mut());
+            hash::scrub(cipher_text_cmp_bytes.as_mut());
+            (
+                fail as u32,
+                hash::h::<S::Hash, _, GenericArray<u8, U32>>(cipher_text),
+            )
         };
-        let cipher_text_bytes = cipher_text.clone_line();
-        let cipher_text_cmp_bytes = cipher_text_cmp.clone_line();
-        let fail = c_cmp(cipher_text_bytes.as_ref(), cipher_text_cmp_bytes.as_ref());
-        c_mov(
-            b0.as_mut(),
-            secret_key.reject.as_ref(),
-            if fail == 0 { 0 } else { 0xff },
-        );
-        hash::h::<D, _, _>(&Concat(b0, cipher_text.clone()))
+        // the re-encryption is compared as it goes, the cipher text is hashed by chunks
+        #[cfg(feature = "low-memory")]
+        let (fail, cipher_text_hash) = {
+            let pk = &secret_key.pk;
+            let fail = stream::diff::<N, S, P>(
+                &pk.seed,
+                &b1,
+                &pk.pk,
+                &message,
+                &cipher_text.pk,
+                &cipher_text.ct,
+            ) | (c_cmp(b2.as_ref(), cipher_text.check.as_ref()) as u32);
+            let mut hasher = S::Hash::default();
+            cipher_text.pk.encode_with(|chunk| hasher.update(chunk));
+            hasher.update(&cipher_text.ct[..]);
+            hasher.update(&cipher_text.check[..]);
+            let mut cipher_text_hash = GenericArray::<u8, U32>::default();
+            hasher.finalize_xof().read(cipher_text_hash.as_mut());
+            (fail, cipher_text_hash)
+        };
+
+        let mut key = Concat(b0, cipher_text_hash);
+        // `1` if the cipher texts differ, `0` otherwise, the negation is the mask of `c_mov`
+        let fail = (fail.wrapping_neg() >> 31) as u8;
+        c_mov(key.0.as_mut(), secret_key.reject.as_ref(), fail.wrapping_neg());
+        let shared_secret = hash::h::<S::Hash, _, _>(&key);
+
+        hash::scrub(message.as_mut());
+        hash::scrub((input.0).1.as_mut());
+        hash::scrub(b1.as_mut());
+        hash::scrub(key.0.as_mut());
+        shared_secret
+    }
+}
+
+impl<const N: usize, S, P> KemExt<S::Hash> for Cca<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    fn encapsulate_key(
+        seed: &GenericArray<u8, Self::EncapsulationSeedLength>,
+        public_key: &Self::PublicKey,
+    ) -> (Self::CipherText, GenericArray<u8, Self::SharedSecretLength>) {
+        <Self as Kem<S::Hash>>::encapsulate(seed, public_key, &public_key.pk_hash)
+    }
+
+    fn decapsulate_key(
+        secret_key: &Self::SecretKey,
+        cipher_text: &Self::CipherText,
+    ) -> GenericArray<u8, Self::SharedSecretLength> {
+        <Self as Kem<S::Hash>>::decapsulate(secret_key, &secret_key.pk.pk_hash, cipher_text)
     }
 }
 
 mod codable {
     #[rustfmt::skip]
     use super::{
-        PolySize, Parameter, Pke,
+        Ring, PolySize, ParameterSet, Symmetric,
         PublicKeyCca, PublicKey,
         SecretKeyCca, SecretKey,
         CipherTextCca,
     };
+    use crate::{Error, Decode, hash};
     use rac::{
-        LineValid, Concat,
-        generic_array::{GenericArray, typenum::U32},
+        LineValid,
+        generic_array::{
+            GenericArray,
+            typenum::{Unsigned, U32},
+        },
     };
 
-    type PkBytes<N> = Concat<PublicKey<N>, GenericArray<u8, <Parameter<N> as Pke>::Seed>>;
-    type SkBytes<N> = Concat<SecretKey<N>, GenericArray<u8, U32>>;
-    type CtTemp<N> = Concat<PublicKey<N>, GenericArray<u8, <N as PolySize>::CompressedLength>>;
-    type CtBytes<N> = Concat<CtTemp<N>, GenericArray<u8, U32>>;
+    impl<const N: usize, S, P> Decode for PublicKeyCca<N, S, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            let p = <Ring<N, P> as PolySize>::PackedLength::USIZE;
+            Error::check_length(bytes, p + U32::USIZE)?;
+            let pk = PublicKey::decode(&bytes[..p])?;
+            let seed = bytes[p..].iter().copied().collect();
+            Ok(PublicKeyCca::new(pk, None, seed))
+        }
+    }
 
-    impl<N> LineValid for PublicKeyCca<N>
+    impl<const N: usize, S, P> Decode for SecretKeyCca<N, S, P>
     where
-        N: PolySize,
-        Parameter<N>: Pke<Seed = U32>,
-        PublicKey<N>: Clone + LineValid,
-        PkBytes<N>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
     {
-        type Length = <PkBytes<N> as LineValid>::Length;
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            let p = <Ring<N, P> as PolySize>::PackedLength::USIZE;
+            let s = U32::USIZE;
+            Error::check_length(bytes, p + (p + s) + s + s)?;
+            let sk = SecretKey::decode(&bytes[..p])?;
+            let pk = PublicKeyCca::decode(&bytes[p..(2 * p + s)]).map_err(|e| e.shift(p, 0))?;
+            // whether the public key is generated from the secret needs the parameter,
+            // it is up to `SecretKeyCca::validate`
+            if pk.pk_hash[..] != bytes[(2 * p + s)..(2 * p + 2 * s)] {
+                return Err(Error::InconsistentPublicKey);
+            }
+            Ok(SecretKeyCca {
+                sk: sk,
+                pk: pk,
+                reject: bytes[(2 * p + 2 * s)..].iter().copied().collect(),
+            })
+        }
+    }
 
-        fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            PkBytes::try_clone_array(a).map(|Concat(pk, seed)| PublicKeyCca {
+    impl<const N: usize, P> Decode for CipherTextCca<N, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            let p = <Ring<N, P> as PolySize>::PackedLength::USIZE;
+            let c = <Ring<N, P> as PolySize>::CompressedLength::USIZE;
+            Error::check_length(bytes, p + c + U32::USIZE)?;
+            let pk = PublicKey::decode(&bytes[..p])?;
+            Ok(CipherTextCca {
                 pk: pk,
-                parameter: Parameter::new(&seed),
-                seed: seed,
+                ct: bytes[p..(p + c)].iter().copied().collect(),
+                check: bytes[(p + c)..].iter().copied().collect(),
             })
         }
+    }
+
+    impl<const N: usize, S, P> LineValid for PublicKeyCca<N, S, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        type Length = <Ring<N, P> as PolySize>::PublicKeyLength;
+
+        fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
+            Self::decode(a).map_err(|_| ())
+        }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
-            Concat(self.pk.clone(), self.seed.clone()).clone_line()
+            hash::join(&[&self.pk.clone_line(), &self.seed])
         }
     }
 
-    impl<N> LineValid for SecretKeyCca<N>
+    impl<const N: usize, S, P> LineValid for SecretKeyCca<N, S, P>
     where
-        N: PolySize,
-        SecretKey<N>: Clone + LineValid,
-        PublicKeyCca<N>: Clone + LineValid,
-        Concat<SecretKey<N>, PublicKeyCca<N>>: LineValid,
-        SkBytes<N>: LineValid,
-        Concat<SkBytes<N>, PublicKeyCca<N>>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
     {
-        type Length = <Concat<SkBytes<N>, PublicKeyCca<N>> as LineValid>::Length;
+        type Length = <Ring<N, P> as PolySize>::SecretKeyCcaLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            Concat::<SkBytes<N>, PublicKeyCca<N>>::try_clone_array(a)
-                .map(|Concat(Concat(sk, reject), pk)| {
-                    SecretKeyCca {
-                        sk: sk,
-                        reject: reject,
-                        pk: pk,
-                    }
-                })
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
-            Concat(
-                Concat(self.sk.clone(), self.reject.clone()),
-                self.pk.clone(),
-            )
-            .clone_line()
+            let mut sk = self.sk.clone_line();
+            let bytes = hash::join(&[&sk, &self.pk.clone_line(), &self.pk.pk_hash, &self.reject]);
+            hash::scrub(sk.as_mut());
+            bytes
         }
     }
 
-    impl<N> LineValid for CipherTextCca<N>
+    impl<const N: usize, P> LineValid for CipherTextCca<N, P>
     where
-        N: PolySize,
-        PublicKey<N>: Clone + LineValid,
-        CtTemp<N>: LineValid,
-        CtBytes<N>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
     {
-        type Length = <CtBytes<N> as LineValid>::Length;
+        type Length = <Ring<N, P> as PolySize>::CipherTextCcaLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            CtBytes::try_clone_array(a).map(|Concat(Concat(pk, ct), check)| CipherTextCca {
-                pk: pk,
-                ct: ct,
-                check: check,
-            })
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
-            Concat(Concat(self.pk.clone(), self.ct.clone()), self.check.clone()).clone_line()
+            hash::join(&[&self.pk.clone_line(), &self.ct, &self.check])
         }
     }
 }
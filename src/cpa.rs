@@ -1,66 +1,190 @@
 use super::{
-    hash,
-    poly::PolySize,
+    Error, KemExt, Symmetric, Keccak, hash,
+    poly::{Ring, PolySize, ParameterSet, NewHope},
     pke::{Pke, PublicKey, SecretKey, Parameter},
 };
-use core::marker::PhantomData;
+use core::{fmt, marker::PhantomData};
 use rac::{
-    Concat, LineValid,
+    Concat,
     generic_array::{GenericArray, typenum::U32},
 };
-use sha3::digest::{Update, ExtendableOutput};
 use pq_kem::Kem;
+#[cfg(feature = "low-memory")]
+use super::pke::stream;
 
-pub struct Cpa<N>(PhantomData<N>)
+/// The cpa variant, the symmetric primitives are `S`, the parameter set is `P`.
+pub struct Cpa<const N: usize, S = Keccak, P = NewHope>(PhantomData<(S, P)>)
 where
-    N: PolySize;
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric;
 
 #[derive(Clone)]
-pub struct PublicKeyCpa<N>
+pub struct PublicKeyCpa<const N: usize, S = Keccak, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
-    pk: PublicKey<N>,
-    parameter: Parameter<N>,
+    pk: PublicKey<N, P>,
+    // kept once expanded by `expand`, otherwise expanded for each use
+    parameter: Option<Parameter<N, S, P>>,
     seed: GenericArray<u8, U32>,
 }
 
-#[derive(Clone)]
-pub struct SecretKeyCpa<N>
+pub struct SecretKeyCpa<const N: usize, P = NewHope>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    sk: SecretKey<N, P>,
+}
+
+impl<const N: usize, P> fmt::Debug for SecretKeyCpa<N, P>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
 {
-    sk: SecretKey<N>,
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKeyCpa(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, P> zeroize::Zeroize for SecretKeyCpa<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    fn zeroize(&mut self) {
+        self.sk.zeroize()
+    }
 }
 
 #[derive(Clone)]
-pub struct CipherTextCpa<N>
+pub struct CipherTextCpa<const N: usize, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
 {
-    pk: PublicKey<N>,
-    ct: GenericArray<u8, N::CompressedLength>,
+    pk: PublicKey<N, P>,
+    ct: GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
 }
 
-impl<N, D> Kem<D> for Cpa<N>
+impl<const N: usize, S, P> PublicKeyCpa<N, S, P>
 where
-    D: Default + Update + ExtendableOutput,
-    N: PolySize,
-    PublicKeyCpa<N>: LineValid,
-    SecretKeyCpa<N>: LineValid,
-    CipherTextCpa<N>: LineValid,
-    Parameter<N>: Pke<
-        Seed = U32,
-        GenerationSeed = U32,
-        Plain = U32,
-        Cipher = N::CompressedLength,
-        PublicKey = PublicKey<N>,
-        SecretKey = SecretKey<N>,
-    >,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
-    type PublicKey = PublicKeyCpa<N>;
-    type SecretKey = SecretKeyCpa<N>;
-    type CipherText = CipherTextCpa<N>;
+    /// Expands the public parameter from the seed and keeps it, the key that is
+    /// not expanded expands the parameter for each encapsulation, and decoding
+    /// does not expand it.
+    pub fn expand(&mut self) {
+        if self.parameter.is_none() {
+            self.parameter = Some(Parameter::new(&self.seed));
+        }
+    }
+
+    /// The public parameter if it is expanded.
+    pub fn parameter(&self) -> Option<&Parameter<N, S, P>> {
+        self.parameter.as_ref()
+    }
+
+    /// Whether the public parameter is already expanded.
+    pub fn is_expanded(&self) -> bool {
+        self.parameter.is_some()
+    }
+
+    /// Expands and precomputes the public parameter, it is worth it
+    /// if the key encapsulates many times, see `Parameter::precompute`.
+    pub fn precompute(&mut self) {
+        self.expand();
+        if let Some(parameter) = &mut self.parameter {
+            parameter.precompute();
+        }
+    }
+
+    /// Whether the public parameter is precomputed.
+    pub fn is_precomputed(&self) -> bool {
+        self.parameter
+            .as_ref()
+            .is_some_and(Parameter::is_precomputed)
+    }
+
+    // the kept parameter, or the one expanded for this use only
+    fn with_parameter<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Parameter<N, S, P>) -> R,
+    {
+        match &self.parameter {
+            Some(parameter) => f(parameter),
+            None => f(&Parameter::new(&self.seed)),
+        }
+    }
+
+    /// Checks that the parameter is expanded from the seed, does not expand it.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.pk.validate()?;
+        match &self.parameter {
+            Some(parameter) => parameter.validate(&self.seed),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<const N: usize, P> SecretKeyCpa<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// Checks that the secret is a noise polynomial.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.sk.validate()
+    }
+
+    /// Precomputes the secret, it is worth it if the key decapsulates many times.
+    pub fn precompute(&mut self) {
+        self.sk.precompute();
+    }
+
+    /// Whether the secret is precomputed.
+    pub fn is_precomputed(&self) -> bool {
+        self.sk.is_precomputed()
+    }
+
+    /// Checks that the secret key corresponds to the public key.
+    pub fn validate_pair<S>(&self, public_key: &PublicKeyCpa<N, S, P>) -> Result<(), Error>
+    where
+        S: Symmetric,
+    {
+        self.validate()?;
+        public_key.validate()?;
+        public_key.with_parameter(|parameter| public_key.pk.validate_pair(parameter, &self.sk))
+    }
+}
+
+impl<const N: usize, P> CipherTextCpa<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// Checks that every coefficient of the cipher text is reduced.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.pk.validate()
+    }
+}
+
+// the same hash as the cca variant, so the suite fixes every primitive
+impl<const N: usize, S, P> Kem<S::Hash> for Cpa<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    type PublicKey = PublicKeyCpa<N, S, P>;
+    type SecretKey = SecretKeyCpa<N, P>;
+    type CipherText = CipherTextCpa<N, P>;
     type PairSeedLength = U32;
     type PublicKeyHashLength = U32;
     type EncapsulationSeedLength = U32;
@@ -69,15 +193,25 @@ where
     fn generate_pair(
         seed: &GenericArray<u8, Self::PairSeedLength>,
     ) -> (Self::PublicKey, Self::SecretKey) {
-        let Concat(public_seed, noise_seed) = hash::h::<D, _, _>(&Concat(hash::B(1), seed.clone()));
+        let Concat(public_seed, noise_seed) =
+            hash::h::<S::Hash, _, _>(&Concat(hash::B(1), *seed));
 
-        let parameter = Parameter::new(&public_seed);
-        let (pk, sk) = parameter.generate(&noise_seed);
+        #[cfg(not(feature = "low-memory"))]
+        let (pk, sk, parameter) = {
+            let parameter = Parameter::new(&public_seed);
+            let (pk, sk) = parameter.generate(&noise_seed);
+            (pk, sk, Some(parameter))
+        };
+        #[cfg(feature = "low-memory")]
+        let (pk, sk, parameter) = {
+            let (pk, sk) = stream::generate::<N, S, P>(&public_seed, &noise_seed);
+            (pk, sk, None)
+        };
         (
             PublicKeyCpa {
                 pk: pk,
                 parameter: parameter,
-                seed: seed.clone(),
+                seed: public_seed,
             },
             SecretKeyCpa { sk: sk },
         )
@@ -89,11 +223,18 @@ where
         public_key_hash: &GenericArray<u8, Self::PublicKeyHashLength>,
     ) -> (Self::CipherText, GenericArray<u8, Self::SharedSecretLength>) {
         let _ = public_key_hash;
-        let Concat(message, noise_seed) = hash::h::<D, _, _>(&Concat(hash::B(2), seed.clone()));
+        let Concat(mut message, mut noise_seed) =
+            hash::h::<S::Hash, _, _>(&Concat(hash::B(2), *seed));
+        #[cfg(not(feature = "low-memory"))]
         let (pk, cipher) = public_key
-            .parameter
-            .encrypt(&noise_seed, &public_key.pk, &message);
-        (CipherTextCpa { pk: pk, ct: cipher }, hash::h::<D, _, _>(&message))
+            .with_parameter(|parameter| parameter.encrypt(&noise_seed, &public_key.pk, &message));
+        #[cfg(feature = "low-memory")]
+        let (pk, cipher) =
+            stream::encrypt::<N, S, P>(&public_key.seed, &noise_seed, &public_key.pk, &message);
+        let shared_secret = hash::h::<S::Hash, _, _>(&message);
+        hash::scrub(message.as_mut());
+        hash::scrub(noise_seed.as_mut());
+        (CipherTextCpa { pk: pk, ct: cipher }, shared_secret)
     }
 
     fn decapsulate(
@@ -102,58 +243,125 @@ where
         cipher_text: &Self::CipherText,
     ) -> GenericArray<u8, Self::SharedSecretLength> {
         let _ = public_key_hash;
-        let message = Parameter::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
-        hash::h::<D, _, _>(&message)
+        #[cfg(not(feature = "low-memory"))]
+        let mut message =
+            Parameter::<N, S, P>::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
+        #[cfg(feature = "low-memory")]
+        let mut message = stream::decrypt(&cipher_text.pk, &secret_key.sk, &cipher_text.ct);
+        let shared_secret = hash::h::<S::Hash, _, _>(&message);
+        hash::scrub(message.as_mut());
+        shared_secret
+    }
+}
+
+impl<const N: usize, S, P> KemExt<S::Hash> for Cpa<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    // the cpa variant does not use the hash of the public key
+    fn encapsulate_key(
+        seed: &GenericArray<u8, Self::EncapsulationSeedLength>,
+        public_key: &Self::PublicKey,
+    ) -> (Self::CipherText, GenericArray<u8, Self::SharedSecretLength>) {
+        <Self as Kem<S::Hash>>::encapsulate(seed, public_key, &GenericArray::default())
+    }
+
+    fn decapsulate_key(
+        secret_key: &Self::SecretKey,
+        cipher_text: &Self::CipherText,
+    ) -> GenericArray<u8, Self::SharedSecretLength> {
+        <Self as Kem<S::Hash>>::decapsulate(secret_key, &GenericArray::default(), cipher_text)
     }
 }
 
 mod codable {
     #[rustfmt::skip]
     use super::{
-        PolySize, Parameter, Pke,
+        Ring, PolySize, ParameterSet, Symmetric,
         PublicKeyCpa, PublicKey,
         SecretKeyCpa, SecretKey,
         CipherTextCpa,
     };
+    use crate::{Error, Decode, hash};
     use rac::{
-        LineValid, Concat,
-        generic_array::{GenericArray, typenum::U32},
+        LineValid,
+        generic_array::{
+            GenericArray,
+            typenum::{Unsigned, U32},
+        },
     };
 
-    type PkBytes<N> = Concat<PublicKey<N>, GenericArray<u8, <Parameter<N> as Pke>::Seed>>;
-    type CtBytes<N> = Concat<PublicKey<N>, GenericArray<u8, <N as PolySize>::CompressedLength>>;
-
-    impl<N> LineValid for PublicKeyCpa<N>
+    impl<const N: usize, S, P> Decode for PublicKeyCpa<N, S, P>
     where
-        N: PolySize,
-        Parameter<N>: Pke<Seed = U32>,
-        PublicKey<N>: Clone + LineValid,
-        PkBytes<N>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
     {
-        type Length = <PkBytes<N> as LineValid>::Length;
-
-        fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            PkBytes::try_clone_array(a).map(|Concat(pk, seed)| PublicKeyCpa {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            let p = <Ring<N, P> as PolySize>::PackedLength::USIZE;
+            Error::check_length(bytes, p + U32::USIZE)?;
+            let pk = PublicKey::decode(&bytes[..p])?;
+            let seed = bytes[p..].iter().copied().collect();
+            Ok(PublicKeyCpa {
                 pk: pk,
-                parameter: Parameter::new(&seed),
+                parameter: None,
                 seed: seed,
             })
         }
+    }
+
+    impl<const N: usize, P> Decode for SecretKeyCpa<N, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            SecretKey::decode(bytes).map(|sk| SecretKeyCpa { sk: sk })
+        }
+    }
+
+    impl<const N: usize, P> Decode for CipherTextCpa<N, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            let p = <Ring<N, P> as PolySize>::PackedLength::USIZE;
+            Error::check_length(bytes, p + <Ring<N, P> as PolySize>::CompressedLength::USIZE)?;
+            let pk = PublicKey::decode(&bytes[..p])?;
+            let ct = bytes[p..].iter().copied().collect();
+            Ok(CipherTextCpa { pk: pk, ct: ct })
+        }
+    }
+
+    impl<const N: usize, S, P> LineValid for PublicKeyCpa<N, S, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        type Length = <Ring<N, P> as PolySize>::PublicKeyLength;
+
+        fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
+            Self::decode(a).map_err(|_| ())
+        }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
-            Concat(self.pk.clone(), self.seed.clone()).clone_line()
+            hash::join(&[&self.pk.clone_line(), &self.seed])
         }
     }
 
-    impl<N> LineValid for SecretKeyCpa<N>
+    impl<const N: usize, P> LineValid for SecretKeyCpa<N, P>
     where
-        N: PolySize,
-        SecretKey<N>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
     {
-        type Length = <SecretKey<N> as LineValid>::Length;
+        type Length = <Ring<N, P> as PolySize>::PackedLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            SecretKey::try_clone_array(a).map(|sk| SecretKeyCpa { sk: sk })
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
@@ -161,20 +369,19 @@ mod codable {
         }
     }
 
-    impl<N> LineValid for CipherTextCpa<N>
+    impl<const N: usize, P> LineValid for CipherTextCpa<N, P>
     where
-        N: PolySize,
-        PublicKey<N>: Clone + LineValid,
-        CtBytes<N>: LineValid,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
     {
-        type Length = <CtBytes<N> as LineValid>::Length;
+        type Length = <Ring<N, P> as PolySize>::CipherTextLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            CtBytes::try_clone_array(a).map(|Concat(pk, ct)| CipherTextCpa { pk: pk, ct: ct })
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
-            Concat(self.pk.clone(), self.ct.clone()).clone_line()
+            hash::join(&[&self.pk.clone_line(), &self.ct])
         }
     }
 }
@@ -0,0 +1,92 @@
+use core::fmt;
+
+/// Why the bytes or the key is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The input is `actual` bytes long, but the encoding is `expected` bytes.
+    WrongLength { expected: usize, actual: usize },
+    /// The coefficient number `index` of the polynomial starting at the byte `offset`
+    /// is `value`, which is not less than `q`.
+    InvalidCoefficient {
+        offset: usize,
+        index: usize,
+        value: u16,
+    },
+    /// The public parameter is not expanded from the public seed.
+    InconsistentParameter,
+    /// The public key embedded in the secret key is not generated from the secret
+    /// or does not match the embedded hash.
+    InconsistentPublicKey,
+    /// The secret is not a noise polynomial.
+    InvalidSecret,
+    /// The secret key does not correspond to the public key.
+    KeyMismatch,
+}
+
+impl Error {
+    pub(crate) fn check_length(bytes: &[u8], expected: usize) -> Result<(), Self> {
+        if bytes.len() == expected {
+            Ok(())
+        } else {
+            Err(Error::WrongLength {
+                expected,
+                actual: bytes.len(),
+            })
+        }
+    }
+
+    // the position of the field inside the enclosing structure
+    pub(crate) fn shift(self, by_offset: usize, by_index: usize) -> Self {
+        match self {
+            Error::InvalidCoefficient {
+                offset,
+                index,
+                value,
+            } => Error::InvalidCoefficient {
+                offset: offset + by_offset,
+                index: index + by_index,
+                value,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WrongLength { expected, actual } => {
+                write!(f, "wrong length {}, expected {} bytes", actual, expected)
+            },
+            Error::InvalidCoefficient {
+                offset,
+                index,
+                value,
+            } => write!(
+                f,
+                "coefficient {} of the polynomial at byte {} is {}, not less than q",
+                index, offset, value,
+            ),
+            Error::InconsistentParameter => {
+                write!(f, "the parameter is not expanded from the seed")
+            },
+            Error::InconsistentPublicKey => {
+                write!(f, "the embedded public key does not match the secret key")
+            },
+            Error::InvalidSecret => write!(f, "the secret is not a noise polynomial"),
+            Error::KeyMismatch => write!(f, "the secret key does not match the public key"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Decoding which reports why the bytes are rejected,
+/// `LineValid::try_clone_array` is the same, but loses the reason.
+pub trait Decode
+where
+    Self: Sized,
+{
+    fn decode(bytes: &[u8]) -> Result<Self, Error>;
+}
@@ -1,6 +1,6 @@
 use rac::{
     Line, LineValid,
-    generic_array::{GenericArray, typenum::U1},
+    generic_array::{GenericArray, ArrayLength, typenum::U1},
 };
 use sha3::digest::{Update, ExtendableOutput};
 
@@ -34,3 +34,25 @@ where
 
     Line::clone_array(&buffer)
 }
+
+/// Concatenates the encodings, their lengths sum up to `L`.
+pub fn join<L>(parts: &[&[u8]]) -> GenericArray<u8, L>
+where
+    L: ArrayLength<u8>,
+{
+    let mut r = GenericArray::default();
+    let mut offset = 0;
+    for part in parts {
+        r[offset..(offset + part.len())].clone_from_slice(part);
+        offset += part.len();
+    }
+    r
+}
+
+/// Wipes the secret buffer, does nothing without the `zeroize` feature.
+pub fn scrub(buffer: &mut [u8]) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buffer);
+    #[cfg(not(feature = "zeroize"))]
+    let _ = buffer;
+}
@@ -0,0 +1,20 @@
+use rac::generic_array::GenericArray;
+use sha3::digest::{Update, ExtendableOutput};
+use pq_kem::Kem;
+
+/// The key encapsulation whose keys carry the hash of the public key,
+/// so the caller never computes it and cannot pass a wrong one.
+pub trait KemExt<D>
+where
+    Self: Kem<D>,
+    D: Default + Update + ExtendableOutput,
+{
+    fn encapsulate_key(
+        seed: &GenericArray<u8, Self::EncapsulationSeedLength>,
+        public_key: &Self::PublicKey,
+    ) -> (Self::CipherText, GenericArray<u8, Self::SharedSecretLength>);
+    fn decapsulate_key(
+        secret_key: &Self::SecretKey,
+        cipher_text: &Self::CipherText,
+    ) -> GenericArray<u8, Self::SharedSecretLength>;
+}
@@ -0,0 +1,34 @@
+use super::{Parameter, Cpa, Cca};
+use rac::generic_array::typenum::{Unsigned, U256, U512, U1024, U2048};
+
+/// The sizes of the former API, which took the size as a `typenum` number.
+/// The types are parameterized by a constant now, `Cca<U1024>` is `Cca<1024>`,
+/// and the code generic over `N: Legacy` names it `N::Cca`, see the crate docs
+/// and `CHANGELOG.md` for the other types.
+pub trait Legacy
+where
+    Self: Unsigned,
+{
+    type Parameter;
+    type Cpa;
+    type Cca;
+}
+
+macro_rules! legacy {
+    ($($length:ty => $n:literal),* $(,)?) => {
+        $(
+            impl Legacy for $length {
+                type Parameter = Parameter<$n>;
+                type Cpa = Cpa<$n>;
+                type Cca = Cca<$n>;
+            }
+        )*
+    };
+}
+
+legacy! {
+    U256 => 256,
+    U512 => 512,
+    U1024 => 1024,
+    U2048 => 2048,
+}
@@ -1,23 +1,80 @@
+//! NewHope, the key encapsulation based on the Ring-LWE problem.
+//!
+//! # Migration from 0.4
+//!
+//! The ring size is a constant now, not a `typenum` number, replace
+//! `Cpa<U1024>` by `Cpa<1024>`, `Cca<U1024>` by `Cca<1024>`, `Parameter<U1024>`
+//! by `Parameter<1024>`, and likewise for 512. The states of `Poly` are named,
+//! the bits `B0` and `B1` are `Natural` and `Reversed` for the order,
+//! `Small` and `Uniform` for the size, `Time` and `Frequency` for the domain.
+//! The code generic over a `typenum` size names the types by `Legacy`:
+//!
+//! ```
+//! use pq_newhope::{Legacy, generic_array::typenum::U1024};
+//! use pq_kem::Kem;
+//! use sha3::Shake256;
+//!
+//! fn public_key<N>() -> <N::Cca as Kem<Shake256>>::PublicKey
+//! where
+//!     N: Legacy,
+//!     N::Cca: Kem<Shake256>,
+//! {
+//!     let (pk, _) = <N::Cca as Kem<Shake256>>::generate_pair(&Default::default());
+//!     pk
+//! }
+//!
+//! let _ = public_key::<U1024>();
+//! ```
+//!
+//! `CHANGELOG.md` lists the replacement of each type.
+
 #![no_std]
+// the vectorized kernels are the only unsafe code, they are compiled only with `std`
+// on x86, so the portable build forbids unsafe code, and the rest denies it
+#![cfg_attr(
+    not(any(test, all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))),
+    forbid(unsafe_code)
+)]
+#![cfg_attr(
+    any(test, all(feature = "std", any(target_arch = "x86", target_arch = "x86_64"))),
+    deny(unsafe_code)
+)]
 
 pub use rac::generic_array;
 
-#[cfg(any(test, feature = "smallest"))]
+#[cfg(any(test, feature = "smallest", feature = "std"))]
 extern crate std;
 
+mod error;
+pub use self::error::{Error, Decode};
+
 mod hash;
 pub use self::hash::h;
 
 pub mod poly;
 
+#[cfg(feature = "std")]
+pub mod analysis;
+
+mod symmetric;
+pub use self::symmetric::{Symmetric, Keccak};
+#[cfg(feature = "90s")]
+pub use self::symmetric::{Aes, Sha2};
+
 mod pke;
 pub use self::pke::{Pke, Parameter};
 
+mod kem;
+pub use self::kem::KemExt;
+
 mod cpa;
 pub use self::cpa::Cpa;
 
 mod cca;
 pub use self::cca::Cca;
 
+mod legacy;
+pub use self::legacy::Legacy;
+
 #[cfg(test)]
 mod tests;
@@ -1,26 +1,66 @@
-use super::poly::{Poly, PolySize, FromSeed, FromSeedSmall, ReverseBits, Ntt};
+use super::{
+    Error, Symmetric, Keccak,
+    poly::{
+        Poly, Precomputed, Ring, PolySize, ParameterSet, NewHope, FromSeed, FromSeedSmall, Ntt,
+        Margin, Natural, Reversed, Small, Uniform, Time, Frequency,
+    },
+};
+use core::{fmt, marker::PhantomData};
 use rac::{
     LineValid,
-    generic_array::{
-        GenericArray, ArrayLength,
-        typenum::{U32, B0, B1},
-    },
+    generic_array::{GenericArray, ArrayLength, typenum::U32},
 };
 
 #[derive(Clone)]
-pub struct PublicKey<N>(Poly<N, (B0, B0, B1)>)
+pub struct PublicKey<const N: usize, P = NewHope>(Poly<N, (Natural, Small, Frequency), P>)
 where
-    N: PolySize;
+    Ring<N, P>: PolySize,
+    P: ParameterSet;
 
-#[derive(Clone)]
-pub struct SecretKey<N>(Poly<N, (B0, B0, B1)>)
+pub struct SecretKey<const N: usize, P = NewHope>(
+    Poly<N, (Natural, Small, Frequency), P>,
+    Option<Precomputed<N, (Natural, Small, Frequency), P>>,
+)
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet;
+
+impl<const N: usize, P> fmt::Debug for SecretKey<N, P>
 where
-    N: PolySize;
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, P> zeroize::Zeroize for SecretKey<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+        if let Some(s) = self.1.as_mut() {
+            s.zeroize();
+        }
+    }
+}
 
+/// The public parameter, `S` are the primitives it is expanded with,
+/// `P` is the parameter set.
 #[derive(Clone)]
-pub struct Parameter<N>(Poly<N, (B0, B1, B1)>)
+pub struct Parameter<const N: usize, S = Keccak, P = NewHope>(
+    Poly<N, (Natural, Uniform, Frequency), P>,
+    Option<Precomputed<N, (Natural, Uniform, Frequency), P>>,
+    PhantomData<S>,
+)
 where
-    N: PolySize;
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric;
 
 pub trait Pke {
     type Seed: ArrayLength<u8>;
@@ -48,34 +88,31 @@ pub trait Pke {
     ) -> GenericArray<u8, Self::Plain>;
 }
 
-impl<N> Pke for Parameter<N>
+impl<const N: usize, S, P> Pke for Parameter<N, S, P>
 where
-    N: PolySize,
-    Poly<N, (B0, B1, B1)>: FromSeed,
-    Poly<N, (B1, B0, B0)>: FromSeedSmall + Ntt<Output = Poly<N, (B0, B0, B1)>>,
-    Poly<N, (B0, B0, B1)>: Ntt + ReverseBits<Output = Poly<N, (B1, B0, B1)>>,
-    Poly<N, (B1, B0, B1)>: Ntt<Output = Poly<N, (B0, B0, B0)>> + ReverseBits,
-    Poly<N, (B0, B0, B0)>: FromSeed + Ntt,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
 {
     type Seed = U32;
     type GenerationSeed = U32;
     type Plain = U32;
-    type Cipher = N::CompressedLength;
-    type PublicKey = PublicKey<N>;
-    type SecretKey = SecretKey<N>;
+    type Cipher = <Ring<N, P> as PolySize>::CompressedLength;
+    type PublicKey = PublicKey<N, P>;
+    type SecretKey = SecretKey<N, P>;
 
     fn new(seed: &GenericArray<u8, Self::Seed>) -> Self {
-        Parameter(Poly::random(seed))
+        Parameter(Poly::random::<S::Uniform>(seed), None, PhantomData)
     }
 
     fn generate(
         &self,
         seed: &GenericArray<u8, Self::GenerationSeed>,
     ) -> (Self::PublicKey, Self::SecretKey) {
-        let s = Poly::random_small(seed, 0).ntt();
-        let e = Poly::random_small(seed, 1).ntt();
-        let b = Poly::functor_3(&e, &self.0, &s, |e, a, s| e + a * s);
-        (PublicKey(b), SecretKey(s))
+        let s = Poly::<_, (Reversed, Small, Time), P>::random_small::<S::Noise>(seed, 0).ntt();
+        let e = Poly::<_, (Reversed, Small, Time), P>::random_small::<S::Noise>(seed, 1).ntt();
+        let b = &e + &self.product(&s);
+        (PublicKey(b), SecretKey(s, None))
     }
 
     fn encrypt(
@@ -84,13 +121,13 @@ where
         pk_a: &Self::PublicKey,
         plain: &GenericArray<u8, Self::Plain>,
     ) -> (Self::PublicKey, GenericArray<u8, Self::Cipher>) {
-        let v = Poly::from_message(plain);
+        // the inverse transform gives the coefficients in bit reversed order,
+        // the noise and the message are stored in the same order
+        let v = Poly::<_, (Reversed, Small, Time), P>::from_message(plain);
         let (pk_b, sk_b) = self.generate(seed);
-        let e = Poly::<_, (B0, B0, B0)>::random_small(seed, 2);
-        let dh = Poly::functor_2(&pk_a.0, &sk_b.0, |pk, sk| pk * sk)
-            .reverse_bits()
-            .inv_ntt();
-        let c = Poly::functor_3(&dh, &e, &v, |dh, e, v| dh + e + v);
+        let e = Poly::<_, (Natural, Small, Time), P>::random_small_reversed::<S::Noise>(seed, 2);
+        let dh = (&pk_a.0 * &sk_b.0).inv_ntt();
+        let c = &(&dh + &e) + &v;
         (pk_b, c.compress())
     }
 
@@ -99,27 +136,334 @@ where
         sk_a: &Self::SecretKey,
         cipher: &GenericArray<u8, Self::Cipher>,
     ) -> GenericArray<u8, Self::Plain> {
-        let dh = Poly::functor_2(&pk_b.0, &sk_a.0, |pk, sk| pk * sk)
-            .reverse_bits()
-            .inv_ntt();
-        let c = Poly::<_, (B0, B0, B0)>::decompress(cipher);
-        let v = Poly::functor_2(&dh, &c, |dh, c| dh - c);
-        v.to_message_negate().into()
+        let dh = sk_a.product(&pk_b.0).inv_ntt();
+        let c = Poly::<_, (Reversed, Small, Time), P>::decompress(cipher);
+        let v = &dh - &c;
+        v.to_message_negate()
+    }
+}
+
+impl<const N: usize, S, P> Parameter<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: Symmetric,
+{
+    /// Checks that the parameter is expanded from the seed.
+    pub fn validate(&self, seed: &GenericArray<u8, U32>) -> Result<(), Error> {
+        if self.0 == Poly::random::<S::Uniform>(seed) {
+            Ok(())
+        } else {
+            Err(Error::InconsistentParameter)
+        }
+    }
+
+    /// Precomputes the parameter for the repeated products in `generate` and
+    /// `encrypt`, it makes them faster, but takes twice as much memory.
+    pub fn precompute(&mut self) {
+        if self.1.is_none() {
+            self.1 = Some(self.0.precompute());
+        }
+    }
+
+    /// The same as `Pke::decrypt`, and the sums the bits of the message are
+    /// decided by, they show how close the decryption came to the failure.
+    /// The sums depend on the secret key, they are for the diagnostics only.
+    pub fn decrypt_with_margin(
+        pk_b: &PublicKey<N, P>,
+        sk_a: &SecretKey<N, P>,
+        cipher: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    ) -> (GenericArray<u8, U32>, Margin) {
+        let dh = sk_a.product(&pk_b.0).inv_ntt();
+        let c = Poly::<_, (Reversed, Small, Time), P>::decompress(cipher);
+        let margin = (&dh - &c).margin_negate();
+        (margin.message(), margin)
+    }
+
+    /// Whether the parameter is precomputed.
+    pub fn is_precomputed(&self) -> bool {
+        self.1.is_some()
+    }
+
+    // `a * s`
+    fn product(
+        &self,
+        s: &Poly<N, (Natural, Small, Frequency), P>,
+    ) -> Poly<N, (Natural, Small, Frequency), P> {
+        match &self.1 {
+            Some(a) => s * a,
+            None => &self.0 * s,
+        }
+    }
+}
+
+impl<const N: usize, P> PublicKey<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// Checks that every coefficient is reduced, the decoding guarantees it.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.0.check_reduced()
+    }
+
+    /// Checks that the public key is `a * s + e` where `e` is a noise polynomial,
+    /// i.e. the public key is generated from the secret key.
+    pub fn validate_pair<S>(
+        &self,
+        parameter: &Parameter<N, S, P>,
+        sk: &SecretKey<N, P>,
+    ) -> Result<(), Error>
+    where
+        S: Symmetric,
+    {
+        let e = Poly::functor_3(&self.0, &parameter.0, &sk.0, |b, a, s| b - a * s).inv_ntt();
+        if e.is_small(P::NOISE.bound() as u16) {
+            Ok(())
+        } else {
+            Err(Error::KeyMismatch)
+        }
+    }
+}
+
+impl<const N: usize, P> SecretKey<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// Checks that the secret key is a noise polynomial in the NTT domain.
+    pub fn validate(&self) -> Result<(), Error> {
+        let s = self.0.clone().inv_ntt();
+        if s.is_small(P::NOISE.bound() as u16) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSecret)
+        }
+    }
+
+    /// Precomputes the secret for the repeated products in `decrypt`.
+    pub(crate) fn precompute(&mut self) {
+        if self.1.is_none() {
+            self.1 = Some(self.0.precompute());
+        }
+    }
+
+    pub(crate) fn is_precomputed(&self) -> bool {
+        self.1.is_some()
+    }
+
+    // `s * b`
+    fn product(
+        &self,
+        b: &Poly<N, (Natural, Small, Frequency), P>,
+    ) -> Poly<N, (Natural, Small, Frequency), P> {
+        match &self.1 {
+            Some(s) => b * s,
+            None => &self.0 * b,
+        }
+    }
+}
+
+impl<const N: usize, P> PublicKey<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    /// The same bytes as `clone_line` given to `f` by chunks.
+    #[cfg(feature = "low-memory")]
+    pub(crate) fn encode_with(&self, f: impl FnMut(&[u8])) {
+        self.0.pack_with(f)
+    }
+}
+
+/// The encryption for the low memory mode. The public parameter is expanded from
+/// its seed block by block right into the product, the transforms work in place,
+/// so at most two polynomials are alive at once. Gives exactly the same bytes
+/// as `Pke`, but ignores the precomputed factors. The decapsulation of
+/// NewHope-1024 CCA takes about 7 KiB of stack with either the portable or
+/// the AVX2 kernels, `tests/stack.rs` checks both against 8 KiB.
+#[cfg(feature = "low-memory")]
+pub(crate) mod stream {
+    use super::{PublicKey, SecretKey};
+    use crate::{
+        Symmetric,
+        poly::{
+            Ring, PolySize, ParameterSet, Scratch, View, Natural, Reversed, Small, Time, Frequency,
+        },
+    };
+    use rac::generic_array::{GenericArray, typenum::U32};
+
+    pub fn generate<const N: usize, S, P>(
+        parameter_seed: &GenericArray<u8, U32>,
+        seed: &GenericArray<u8, U32>,
+    ) -> (PublicKey<N, P>, SecretKey<N, P>)
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        let (mut s_scratch, mut b_scratch) = (Scratch::default(), Scratch::default());
+        let s = View::<N, (Reversed, Small, Time), P>::random_small::<S::Noise>(
+            &mut s_scratch,
+            seed,
+            0,
+        )
+        .ntt();
+        let mut b = View::<N, (Reversed, Small, Time), P>::random_small::<S::Noise>(
+            &mut b_scratch,
+            seed,
+            1,
+        )
+        .ntt();
+        b.add_product_uniform::<S::Uniform>(parameter_seed, &s);
+        (PublicKey(b.to_poly()), SecretKey(s.to_poly(), None))
+    }
+
+    // gives the public key of the sender to `on_public_key` and the polynomial
+    // to compress to `on_cipher`, they do not need to be stored
+    fn encrypt_with<const N: usize, S, P, K, C, A, B>(
+        parameter_seed: &GenericArray<u8, U32>,
+        seed: &GenericArray<u8, U32>,
+        pk_a: &PublicKey<N, P>,
+        plain: &GenericArray<u8, U32>,
+        on_public_key: K,
+        on_cipher: C,
+    ) -> (A, B)
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+        K: FnOnce(&View<'_, N, (Natural, Small, Frequency), P>) -> A,
+        C: FnOnce(&View<'_, N, (Reversed, Small, Time), P>) -> B,
+    {
+        let (mut s_scratch, mut b_scratch) = (Scratch::default(), Scratch::default());
+        let s = View::<N, (Reversed, Small, Time), P>::random_small::<S::Noise>(
+            &mut s_scratch,
+            seed,
+            0,
+        )
+        .ntt();
+        let mut b = View::<N, (Reversed, Small, Time), P>::random_small::<S::Noise>(
+            &mut b_scratch,
+            seed,
+            1,
+        )
+        .ntt();
+        b.add_product_uniform::<S::Uniform>(parameter_seed, &s);
+        let a = on_public_key(&b);
+
+        let mut c = s.mul(&pk_a.0).inv_ntt();
+        c.add_small::<S::Noise>(seed, 2);
+        c.add_message(plain);
+        (a, on_cipher(&c))
+    }
+
+    pub fn encrypt<const N: usize, S, P>(
+        parameter_seed: &GenericArray<u8, U32>,
+        seed: &GenericArray<u8, U32>,
+        pk_a: &PublicKey<N, P>,
+        plain: &GenericArray<u8, U32>,
+    ) -> (
+        PublicKey<N, P>,
+        GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    )
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        let on_public_key = |b: &View<'_, N, _, P>| PublicKey(b.to_poly());
+        let on_cipher = |c: &View<'_, N, _, P>| c.compress();
+        encrypt_with::<N, S, P, _, _, _, _>(
+            parameter_seed,
+            seed,
+            pk_a,
+            plain,
+            on_public_key,
+            on_cipher,
+        )
+    }
+
+    /// Zero if the encryption gives exactly `pk_b` and `cipher`,
+    /// does not branch on the result of the comparison.
+    pub fn diff<const N: usize, S, P>(
+        parameter_seed: &GenericArray<u8, U32>,
+        seed: &GenericArray<u8, U32>,
+        pk_a: &PublicKey<N, P>,
+        plain: &GenericArray<u8, U32>,
+        pk_b: &PublicKey<N, P>,
+        cipher: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    ) -> u32
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+        S: Symmetric,
+    {
+        let on_public_key = |b: &View<'_, N, _, P>| b.diff(&pk_b.0) as u32;
+        let on_cipher = |c: &View<'_, N, _, P>| c.diff_compressed(cipher) as u32;
+        let (diff_public_key, diff_cipher) = encrypt_with::<N, S, P, _, _, _, _>(
+            parameter_seed,
+            seed,
+            pk_a,
+            plain,
+            on_public_key,
+            on_cipher,
+        );
+        diff_public_key | diff_cipher
+    }
+
+    pub fn decrypt<const N: usize, P>(
+        pk_b: &PublicKey<N, P>,
+        sk_a: &SecretKey<N, P>,
+        cipher: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    ) -> GenericArray<u8, U32>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        let mut scratch = Scratch::default();
+        let mut v = View::product(&mut scratch, &sk_a.0, &pk_b.0).inv_ntt();
+        v.sub_decompressed(cipher);
+        v.to_message_negate()
     }
 }
 
 mod codable {
-    use super::{LineValid, Poly, PolySize, PublicKey, SecretKey};
-    use rac::generic_array::GenericArray;
+    use super::{LineValid, Poly, Ring, PolySize, ParameterSet, PublicKey, SecretKey};
+    use crate::{Error, Decode};
+    use rac::generic_array::{GenericArray, typenum::Unsigned};
+
+    impl<const N: usize, P> Decode for PublicKey<N, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            Error::check_length(bytes, <Ring<N, P> as PolySize>::PackedLength::USIZE)?;
+            Poly::unpack(bytes.into()).map(PublicKey)
+        }
+    }
+
+    impl<const N: usize, P> Decode for SecretKey<N, P>
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        fn decode(bytes: &[u8]) -> Result<Self, Error> {
+            Error::check_length(bytes, <Ring<N, P> as PolySize>::PackedLength::USIZE)?;
+            Poly::unpack(bytes.into()).map(|s| SecretKey(s, None))
+        }
+    }
 
-    impl<N> LineValid for PublicKey<N>
+    impl<const N: usize, P> LineValid for PublicKey<N, P>
     where
-        N: PolySize,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
     {
-        type Length = N::PackedLength;
+        type Length = <Ring<N, P> as PolySize>::PackedLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            Poly::unpack(a).map(PublicKey)
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
@@ -127,14 +471,15 @@ mod codable {
         }
     }
 
-    impl<N> LineValid for SecretKey<N>
+    impl<const N: usize, P> LineValid for SecretKey<N, P>
     where
-        N: PolySize,
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
     {
-        type Length = N::PackedLength;
+        type Length = <Ring<N, P> as PolySize>::PackedLength;
 
         fn try_clone_array(a: &GenericArray<u8, Self::Length>) -> Result<Self, ()> {
-            Poly::unpack(a).map(SecretKey)
+            Self::decode(a).map_err(|_| ())
         }
 
         fn clone_line(&self) -> GenericArray<u8, Self::Length> {
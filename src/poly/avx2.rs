@@ -0,0 +1,659 @@
+// the avx2 kernels, eight coefficients per vector in 32 bit lanes, so the arithmetic
+// is exactly the same as in the portable kernels, the packing is for 14 bits only
+// and the compression for 3 bits only; the shifts are immediate, they are the same
+// for any modulus, so they are taken from the default one
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use super::{
+    coefficient::Coefficient,
+    modulus::Modulus,
+    keccak::{ROUND_CONSTANTS, RHO, PI},
+    tables::reverse_bits,
+    portable,
+};
+use crate::Error;
+
+// `Coefficient` is transparent over `u16`, so the slice is an array of `u16`
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn load<M>(c: &[Coefficient<M>]) -> __m256i
+where
+    M: Modulus,
+{
+    let c = &c[..8];
+    _mm256_cvtepu16_epi32(_mm_loadu_si128(c.as_ptr() as *const __m128i))
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn load_u16(c: &[u16]) -> __m256i {
+    let c = &c[..8];
+    _mm256_cvtepu16_epi32(_mm_loadu_si128(c.as_ptr() as *const __m128i))
+}
+
+// the lanes are less than `2 ^ 16`, so the saturation never happens
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn store<M>(c: &mut [Coefficient<M>], x: __m256i)
+where
+    M: Modulus,
+{
+    let c = &mut c[..8];
+    let x = _mm256_permute4x64_epi64(_mm256_packus_epi32(x, x), 0x08);
+    _mm_storeu_si128(c.as_mut_ptr() as *mut __m128i, _mm256_castsi256_si128(x));
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn quotient<M>(x: __m256i) -> __m256i
+where
+    M: Modulus,
+{
+    let q = _mm256_set1_epi32(M::Q as i32);
+    let t = _mm256_mullo_epi32(x, _mm256_set1_epi32(Coefficient::<M>::BARRETT as i32));
+    let t = _mm256_srli_epi32(t, <Coefficient>::BARRETT_LOG as i32);
+    let r = _mm256_sub_epi32(x, _mm256_mullo_epi32(t, q));
+    let c = _mm256_srli_epi32(_mm256_sub_epi32(_mm256_set1_epi32(M::Q as i32 - 1), r), 31);
+    _mm256_add_epi32(t, c)
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn reduce<M>(x: __m256i) -> __m256i
+where
+    M: Modulus,
+{
+    _mm256_sub_epi32(
+        x,
+        _mm256_mullo_epi32(quotient::<M>(x), _mm256_set1_epi32(M::Q as i32)),
+    )
+}
+
+// the high half of the 64 bit product is the estimation of the quotient,
+// the even and the odd lanes are multiplied separately
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn reduce_wide<M>(x: __m256i) -> __m256i
+where
+    M: Modulus,
+{
+    let q = _mm256_set1_epi32(M::Q as i32);
+    let m = _mm256_set1_epi32(Coefficient::<M>::BARRETT_WIDE as i32);
+    let even = _mm256_srli_epi64(_mm256_mul_epu32(x, m), 32);
+    let odd = _mm256_mul_epu32(_mm256_srli_epi64(x, 32), m);
+    let t = _mm256_blend_epi32(even, odd, 0xaa);
+    let r = _mm256_sub_epi32(x, _mm256_mullo_epi32(t, q));
+    let c = _mm256_srai_epi32(_mm256_sub_epi32(r, q), 31);
+    _mm256_sub_epi32(r, _mm256_andnot_si256(c, q))
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn montgomery_reduce<M>(x: __m256i) -> __m256i
+where
+    M: Modulus,
+{
+    let mask = _mm256_set1_epi32((1 << Coefficient::<M>::R_LOG) - 1);
+    let u = _mm256_mullo_epi32(x, _mm256_set1_epi32(Coefficient::<M>::Q_INV as i32));
+    let u = _mm256_mullo_epi32(_mm256_and_si256(u, mask), _mm256_set1_epi32(M::Q as i32));
+    let low = _mm256_add_epi32(_mm256_and_si256(x, mask), u);
+    _mm256_add_epi32(
+        _mm256_srli_epi32(x, <Coefficient>::R_LOG as i32),
+        _mm256_srli_epi32(low, <Coefficient>::R_LOG as i32),
+    )
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn lazy_sub<M>(a: __m256i, b: __m256i) -> __m256i
+where
+    M: Modulus,
+{
+    let bias = _mm256_set1_epi32(Coefficient::<M>::BIAS as i32);
+    _mm256_sub_epi32(_mm256_add_epi32(a, bias), b)
+}
+
+// both halves of the butterfly, the same as in the portable kernel
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn butterfly<M>(a: __m256i, b: __m256i, w: __m256i, lazy: bool) -> (__m256i, __m256i)
+where
+    M: Modulus,
+{
+    let sum = _mm256_add_epi32(a, b);
+    let sum = if lazy { sum } else { reduce::<M>(sum) };
+    let difference = montgomery_reduce::<M>(_mm256_mullo_epi32(w, lazy_sub::<M>(a, b)));
+    (sum, difference)
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn multiply<M>(c: &mut [Coefficient<M>], gammas: &[u16])
+where
+    M: Modulus,
+{
+    for i in (0..c.len()).step_by(8) {
+        let x = _mm256_mullo_epi32(load_u16(&gammas[i..]), load(&c[i..]));
+        store(&mut c[i..], montgomery_reduce::<M>(x));
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    let n = c.len();
+    let layers = n.trailing_zeros();
+
+    for i in 0..layers {
+        let lazy = i % 2 == 0 && i + 1 != layers;
+        let distance = 1 << i;
+        if distance < 8 {
+            // both coefficients of the butterfly are in the same vector, swap them,
+            // the lower lane takes the sum and the upper lane takes the difference
+            for j in (0..n).step_by(8) {
+                let v = load(&c[j..]);
+                let w = |k: usize| omegas[j / (2 * distance) + k] as i32;
+                let r = match distance {
+                    1 => {
+                        let u = _mm256_shuffle_epi32(v, 0xb1);
+                        let w = _mm256_setr_epi32(w(0), w(0), w(1), w(1), w(2), w(2), w(3), w(3));
+                        let (s, d) = butterfly::<M>(u, v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xaa)
+                    },
+                    2 => {
+                        let u = _mm256_shuffle_epi32(v, 0x4e);
+                        let w = _mm256_setr_epi32(w(0), w(0), w(0), w(0), w(1), w(1), w(1), w(1));
+                        let (s, d) = butterfly::<M>(u, v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xcc)
+                    },
+                    _ => {
+                        let u = _mm256_permute2x128_si256(v, v, 0x01);
+                        let w = _mm256_set1_epi32(w(0));
+                        let (s, d) = butterfly::<M>(u, v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xf0)
+                    },
+                };
+                store(&mut c[j..], r);
+            }
+        } else {
+            for (k, &w) in omegas[..(n / (2 * distance))].iter().enumerate() {
+                let w = _mm256_set1_epi32(w as i32);
+                for j in ((2 * distance * k)..(2 * distance * k + distance)).step_by(8) {
+                    let a = load(&c[j..]);
+                    let b = load(&c[(j + distance)..]);
+                    let (s, d) = butterfly::<M>(a, b, w, lazy);
+                    store(&mut c[j..], s);
+                    store(&mut c[(j + distance)..], d);
+                }
+            }
+        }
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn inverse_transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    let n = c.len();
+    let layers = n.trailing_zeros();
+    let mut offset = 0;
+
+    for i in 0..layers {
+        let lazy = i % 2 == 0 && i + 1 != layers;
+        let distance = n >> (i + 1);
+        let omegas = &omegas[offset..];
+        if distance < 8 {
+            // the same swap as in the forward transform, but the factors
+            // depend on the lane only
+            let w = |k: usize| omegas[k] as i32;
+            let w = match distance {
+                1 => _mm256_set1_epi32(w(0)),
+                2 => _mm256_setr_epi32(w(0), w(1), w(0), w(1), w(0), w(1), w(0), w(1)),
+                _ => _mm256_setr_epi32(w(0), w(1), w(2), w(3), w(0), w(1), w(2), w(3)),
+            };
+            for j in (0..n).step_by(8) {
+                let v = load(&c[j..]);
+                let r = match distance {
+                    1 => {
+                        let (s, d) = butterfly::<M>(_mm256_shuffle_epi32(v, 0xb1), v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xaa)
+                    },
+                    2 => {
+                        let (s, d) = butterfly::<M>(_mm256_shuffle_epi32(v, 0x4e), v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xcc)
+                    },
+                    _ => {
+                        let u = _mm256_permute2x128_si256(v, v, 0x01);
+                        let (s, d) = butterfly::<M>(u, v, w, lazy);
+                        _mm256_blend_epi32(s, d, 0xf0)
+                    },
+                };
+                store(&mut c[j..], r);
+            }
+        } else {
+            for start in (0..n).step_by(2 * distance) {
+                for j in (0..distance).step_by(8) {
+                    let w = load_u16(&omegas[j..]);
+                    let a = load(&c[(start + j)..]);
+                    let b = load(&c[(start + j + distance)..]);
+                    let (s, d) = butterfly::<M>(a, b, w, lazy);
+                    store(&mut c[(start + j)..], s);
+                    store(&mut c[(start + j + distance)..], d);
+                }
+            }
+        }
+        offset += distance;
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn add<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in (0..r.len()).step_by(8) {
+        store(
+            &mut r[i..],
+            reduce::<M>(_mm256_add_epi32(load(&a[i..]), load(&b[i..]))),
+        );
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn sub<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in (0..r.len()).step_by(8) {
+        store(
+            &mut r[i..],
+            reduce::<M>(lazy_sub::<M>(load(&a[i..]), load(&b[i..]))),
+        );
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn mul<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in (0..r.len()).step_by(8) {
+        let x = _mm256_mullo_epi32(load(&a[i..]), load(&b[i..]));
+        store(&mut r[i..], reduce_wide::<M>(x));
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn mul_shoup<M>(
+    r: &mut [Coefficient<M>],
+    a: &[Coefficient<M>],
+    w: &[Coefficient<M>],
+    w_shoup: &[u16],
+) where
+    M: Modulus,
+{
+    let q = _mm256_set1_epi32(M::Q as i32);
+    for i in (0..r.len()).step_by(8) {
+        let x = load(&a[i..]);
+        // the product is less than `2 ^ 32`, the shift is logical
+        let t = _mm256_srli_epi32(_mm256_mullo_epi32(x, load_u16(&w_shoup[i..])), 16);
+        let y = _mm256_sub_epi32(_mm256_mullo_epi32(x, load(&w[i..])), _mm256_mullo_epi32(t, q));
+        let c = _mm256_srai_epi32(_mm256_sub_epi32(y, q), 31);
+        store(&mut r[i..], _mm256_sub_epi32(y, _mm256_andnot_si256(c, q)));
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn pack<M>(c: &[Coefficient<M>], r: &mut [u8])
+where
+    M: Modulus,
+{
+    for i in (0..c.len()).step_by(8) {
+        let x = reduce::<M>(load(&c[i..]));
+        // 28 bits in each 64 bit lane, then 56 bits in each 128 bit lane
+        let low = _mm256_and_si256(x, _mm256_set1_epi64x(0xffff_ffff));
+        let x = _mm256_or_si256(low, _mm256_srli_epi64(x, 18));
+        let x = _mm256_or_si256(x, _mm256_slli_epi64(_mm256_bsrli_epi128(x, 8), 28));
+        let mut t = [0u64; 4];
+        _mm256_storeu_si256(t.as_mut_ptr() as *mut __m256i, x);
+
+        let r = &mut r[(7 * i / 4)..(7 * i / 4 + 14)];
+        r[..7].clone_from_slice(&t[0].to_le_bytes()[..7]);
+        r[7..].clone_from_slice(&t[2].to_le_bytes()[..7]);
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn unpack<M>(v: &[u8], c: &mut [Coefficient<M>]) -> Result<(), Error>
+where
+    M: Modulus,
+{
+    #[rustfmt::skip]
+    let control = _mm256_setr_epi8(
+        0, 1, 2, -1, 1, 2, 3, -1, 3, 4, 5, -1, 5, 6, 7, -1,
+        0, 1, 2, -1, 1, 2, 3, -1, 3, 4, 5, -1, 5, 6, 7, -1,
+    );
+    let shift = _mm256_setr_epi32(0, 6, 4, 2, 0, 6, 4, 2);
+    let mut invalid = _mm256_setzero_si256();
+
+    for i in (0..c.len()).step_by(8) {
+        // the four coefficients of each 128 bit lane are in seven bytes
+        let a = &v[(7 * i / 4)..(7 * i / 4 + 14)];
+        let mut buffer = [0; 32];
+        buffer[..7].clone_from_slice(&a[..7]);
+        buffer[16..23].clone_from_slice(&a[7..]);
+        let x = _mm256_loadu_si256(buffer.as_ptr() as *const __m256i);
+        let x = _mm256_srlv_epi32(_mm256_shuffle_epi8(x, control), shift);
+        let x = _mm256_and_si256(x, _mm256_set1_epi32(0x3fff));
+        invalid = _mm256_or_si256(
+            invalid,
+            _mm256_cmpgt_epi32(x, _mm256_set1_epi32(M::Q as i32 - 1)),
+        );
+        store(&mut c[i..], x);
+    }
+
+    if _mm256_testz_si256(invalid, invalid) == 1 {
+        Ok(())
+    } else {
+        // the portable kernel finds the invalid coefficient
+        portable::unpack(v, c)
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn compress<M>(c: &[Coefficient<M>], a: &mut [u8], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    debug_assert_eq!(bits, 3);
+    let shift = _mm256_setr_epi32(0, 3, 6, 9, 12, 15, 18, 21);
+    for i in (0..c.len()).step_by(8) {
+        let x = if reversed {
+            let at = |j: usize| c[reverse_bits(i + j, c.len())].data() as i32;
+            _mm256_setr_epi32(at(0), at(1), at(2), at(3), at(4), at(5), at(6), at(7))
+        } else {
+            load(&c[i..])
+        };
+        let x = _mm256_slli_epi32(reduce::<M>(x), 3);
+        let x = quotient::<M>(_mm256_add_epi32(x, _mm256_set1_epi32((M::Q as i32) / 2)));
+        let x = _mm256_sllv_epi32(_mm256_and_si256(x, _mm256_set1_epi32(0x07)), shift);
+        // the bits do not overlap, so the sum is the same as bitwise or
+        let x = _mm256_hadd_epi32(x, x);
+        let x = _mm256_hadd_epi32(x, x);
+        let t = _mm_cvtsi128_si32(_mm256_castsi256_si128(x))
+            + _mm_cvtsi128_si32(_mm256_extracti128_si256(x, 1));
+        a[(3 * i / 8)..(3 * i / 8 + 3)].clone_from_slice(&t.to_le_bytes()[..3]);
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn decompress<M>(v: &[u8], c: &mut [Coefficient<M>], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    debug_assert_eq!(bits, 3);
+    let shift = _mm256_setr_epi32(0, 3, 6, 9, 12, 15, 18, 21);
+    let n = c.len();
+    for i in (0..n).step_by(8) {
+        let a = &v[(3 * i / 8)..(3 * i / 8 + 3)];
+        let t = (a[0] as i32) | ((a[1] as i32) << 8) | ((a[2] as i32) << 16);
+        let x = _mm256_srlv_epi32(_mm256_set1_epi32(t), shift);
+        let x = _mm256_mullo_epi32(
+            _mm256_and_si256(x, _mm256_set1_epi32(0x07)),
+            _mm256_set1_epi32(M::Q as i32),
+        );
+        let x = _mm256_srli_epi32(_mm256_add_epi32(x, _mm256_set1_epi32(4)), 3);
+        if reversed {
+            let mut t = [0u32; 8];
+            _mm256_storeu_si256(t.as_mut_ptr() as *mut __m256i, x);
+            for j in 0..8 {
+                c[reverse_bits(i + j, n)] = Coefficient::new(t[j] as u16);
+            }
+        } else {
+            store(&mut c[i..], x);
+        }
+    }
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn rotate(x: __m256i, n: u32) -> __m256i {
+    let left = _mm256_sll_epi64(x, _mm_cvtsi32_si128(n as i32));
+    let right = _mm256_srl_epi64(x, _mm_cvtsi32_si128(64 - n as i32));
+    _mm256_or_si256(left, right)
+}
+
+// each 64 bit lane of the vector is the same lane of the four states, the lanes
+// are loaded from the state and stored back step by step, the state is not copied,
+// so the frame stays small for the low memory mode
+#[target_feature(enable = "avx2")]
+pub unsafe fn f1600x4(state: &mut [[u64; 4]; 25]) {
+    let a = state.as_mut_ptr() as *mut __m256i;
+    let get = |i: usize| _mm256_loadu_si256(a.add(i));
+    let set = |i: usize, x: __m256i| _mm256_storeu_si256(a.add(i), x);
+
+    for rc in &ROUND_CONSTANTS {
+        let mut c = [_mm256_setzero_si256(); 5];
+        for (x, c) in c.iter_mut().enumerate() {
+            let t = _mm256_xor_si256(get(x), get(x + 5));
+            let t = _mm256_xor_si256(t, _mm256_xor_si256(get(x + 10), get(x + 15)));
+            *c = _mm256_xor_si256(t, get(x + 20));
+        }
+        for x in 0..5 {
+            let d = _mm256_xor_si256(c[(x + 4) % 5], rotate(c[(x + 1) % 5], 1));
+            for y in 0..5 {
+                set(5 * y + x, _mm256_xor_si256(get(5 * y + x), d));
+            }
+        }
+
+        let mut last = get(1);
+        for i in 0..24 {
+            let t = get(PI[i]);
+            set(PI[i], rotate(last, RHO[i]));
+            last = t;
+        }
+
+        for y in 0..5 {
+            let row = [
+                get(5 * y),
+                get(5 * y + 1),
+                get(5 * y + 2),
+                get(5 * y + 3),
+                get(5 * y + 4),
+            ];
+            for x in 0..5 {
+                let t = _mm256_andnot_si256(row[(x + 1) % 5], row[(x + 2) % 5]);
+                set(5 * y + x, _mm256_xor_si256(row[x], t));
+            }
+        }
+
+        set(0, _mm256_xor_si256(get(0), _mm256_set1_epi64x(*rc as i64)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        coefficient::Coefficient,
+        modulus::{Modulus, Q12289, Q7681},
+        params::{ParameterSet, NewHope, Toy},
+        portable,
+        poly::Ring,
+        tables::Tables,
+    };
+    use std::{vec, vec::Vec};
+
+    const N: usize = 1024;
+
+    fn detected() -> bool {
+        std::is_x86_feature_detected!("avx2")
+    }
+
+    // any coefficient is less than `2 ^ 15`
+    fn random<M>(n: usize) -> Vec<Coefficient<M>>
+    where
+        M: Modulus,
+    {
+        (0..n)
+            .map(|_| Coefficient::new(rand::random::<u16>() >> 1))
+            .collect()
+    }
+
+    fn raw<M>(c: &[Coefficient<M>]) -> Vec<u32>
+    where
+        M: Modulus,
+    {
+        c.iter().map(Coefficient::data).collect()
+    }
+
+    #[test]
+    fn transform() {
+        if !detected() {
+            return;
+        }
+
+        fn check<const N: usize, P>()
+        where
+            Ring<N, P>: Tables,
+            P: ParameterSet,
+        {
+            let mut a = random::<P::Modulus>(N);
+            let mut b = a.clone();
+            let gammas = Ring::<N, P>::GAMMAS_INV_BITREV_MONTGOMERY;
+            portable::multiply(&mut a, gammas);
+            unsafe { super::multiply(&mut b, gammas) };
+            assert_eq!(raw(&a), raw(&b));
+
+            let gammas = Ring::<N, P>::GAMMAS_BITREV_MONTGOMERY;
+            portable::transform(&mut a, gammas);
+            unsafe { super::transform(&mut b, gammas) };
+            assert_eq!(raw(&a), raw(&b));
+
+            let omegas = Ring::<N, P>::OMEGAS_INV_MONTGOMERY;
+            portable::inverse_transform(&mut a, omegas);
+            unsafe { super::inverse_transform(&mut b, omegas) };
+            assert_eq!(raw(&a), raw(&b));
+        }
+
+        check::<256, NewHope>();
+        check::<512, NewHope>();
+        check::<1024, NewHope>();
+        check::<2048, NewHope>();
+        check::<256, Toy>();
+    }
+
+    #[test]
+    fn f1600x4() {
+        if !detected() {
+            return;
+        }
+
+        let mut a = [[0; 4]; 25];
+        for lane in a.iter_mut().flat_map(|l| l.iter_mut()) {
+            *lane = rand::random();
+        }
+        let mut b = a;
+        portable::f1600x4(&mut a);
+        unsafe { super::f1600x4(&mut b) };
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn arithmetic() {
+        if !detected() {
+            return;
+        }
+
+        arithmetic_with::<Q12289>();
+        arithmetic_with::<Q7681>();
+    }
+
+    fn arithmetic_with<M>()
+    where
+        M: Modulus,
+    {
+        type Kernels<M> = (
+            fn(&mut [Coefficient<M>], &[Coefficient<M>], &[Coefficient<M>]),
+            unsafe fn(&mut [Coefficient<M>], &[Coefficient<M>], &[Coefficient<M>]),
+        );
+
+        let (a, b) = (random::<M>(N), random::<M>(N));
+        let kernels: [Kernels<M>; 3] = [
+            (portable::add, super::add),
+            (portable::sub, super::sub),
+            (portable::mul, super::mul),
+        ];
+        for &(portable, avx2) in &kernels {
+            let mut r_portable = vec![Coefficient::default(); N];
+            let mut r_avx2 = vec![Coefficient::default(); N];
+            portable(&mut r_portable, &a, &b);
+            unsafe { avx2(&mut r_avx2, &a, &b) };
+            assert_eq!(raw(&r_portable), raw(&r_avx2));
+        }
+
+        let w = random::<M>(N)
+            .iter()
+            .map(|c| Coefficient::new(c.freeze() as u16))
+            .collect::<Vec<_>>();
+        let w_shoup = w.iter().map(Coefficient::shoup).collect::<Vec<_>>();
+        let mut r_portable = vec![Coefficient::default(); N];
+        let mut r_avx2 = vec![Coefficient::default(); N];
+        portable::mul_shoup(&mut r_portable, &a, &w, &w_shoup);
+        unsafe { super::mul_shoup(&mut r_avx2, &a, &w, &w_shoup) };
+        assert_eq!(raw(&r_portable), raw(&r_avx2));
+    }
+
+    #[test]
+    fn codec() {
+        if !detected() {
+            return;
+        }
+
+        // any 16 bit value is allowed before the encoding
+        let c = (0..N)
+            .map(|_| <Coefficient>::new(rand::random()))
+            .collect::<Vec<_>>();
+
+        let (mut packed_portable, mut packed_avx2) = (vec![0; N * 7 / 4], vec![0; N * 7 / 4]);
+        portable::pack(&c, &mut packed_portable);
+        unsafe { super::pack(&c, &mut packed_avx2) };
+        assert_eq!(packed_portable, packed_avx2);
+
+        let mut c_avx2 = vec![Coefficient::default(); N];
+        unsafe { super::unpack(&packed_avx2, &mut c_avx2) }.unwrap();
+        assert_eq!(c_avx2, c);
+
+        // random bytes are mostly invalid, both must report the same coefficient
+        for _ in 0..16 {
+            let bytes = (0..(N * 7 / 4))
+                .map(|_| rand::random())
+                .collect::<Vec<u8>>();
+            let mut c_portable = vec![Coefficient::<Q12289>::default(); N];
+            let r_portable = portable::unpack(&bytes, &mut c_portable);
+            let r_avx2 = unsafe { super::unpack(&bytes, &mut c_avx2) };
+            assert_eq!(r_portable, r_avx2);
+        }
+
+        for &reversed in &[false, true] {
+            let (mut compressed_portable, mut compressed_avx2) =
+                (vec![0; N * 3 / 8], vec![0; N * 3 / 8]);
+            portable::compress(&c, &mut compressed_portable, 3, reversed);
+            unsafe { super::compress(&c, &mut compressed_avx2, 3, reversed) };
+            assert_eq!(compressed_portable, compressed_avx2);
+
+            let bytes = (0..(N * 3 / 8))
+                .map(|_| rand::random())
+                .collect::<Vec<u8>>();
+            let mut c_portable = vec![Coefficient::<Q12289>::default(); N];
+            portable::decompress(&bytes, &mut c_portable, 3, reversed);
+            unsafe { super::decompress(&bytes, &mut c_avx2, 3, reversed) };
+            assert_eq!(raw(&c_portable), raw(&c_avx2));
+        }
+    }
+}
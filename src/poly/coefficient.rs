@@ -1,51 +1,141 @@
 use core::{
-    ops::{Add, Sub, Mul, BitOr, BitAnd},
+    ops::{Add, Sub, Mul},
     marker::PhantomData,
+    fmt,
 };
-use super::poly::{PolyState, Involution};
+use super::{
+    state::{PolyState, BitOrder, Size, Domain},
+    modulus::{Modulus, Q12289, negated_inverse, check},
+};
+use crate::Error;
+use rac::generic_array::typenum::Unsigned;
 
-#[derive(Clone, Default, Debug)]
-pub struct Coefficient(u16);
+// The value is lazily reduced, any coefficient is less than `2 ^ 15`, which is more
+// than `2 * q`. Addition, subtraction and multiplication give a result less than `q`,
+// the Montgomery reduction and the butterflies of the transform give less than `2 * q`.
+// The vectorized kernels rely on the layout of `u16`.
+#[repr(transparent)]
+pub struct Coefficient<M = Q12289>(u16, PhantomData<M>)
+where
+    M: Modulus;
 
-impl PartialEq for Coefficient {
-    fn eq(&self, other: &Coefficient) -> bool {
-        self.0 % Self::Q == other.0 % Self::Q
+impl<M> Clone for Coefficient<M>
+where
+    M: Modulus,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.0)
     }
 }
 
-impl Eq for Coefficient {}
+impl<M> Default for Coefficient<M>
+where
+    M: Modulus,
+{
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
 
-impl Coefficient {
-    pub const Q: u16 = 12289;
-    const Q_INV: u16 = 12287;
-    const R_LOG: u32 = 18;
+impl<M> fmt::Debug for Coefficient<M>
+where
+    M: Modulus,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Coefficient").field(&self.0).finish()
+    }
+}
+
+impl<M> PartialEq for Coefficient<M>
+where
+    M: Modulus,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.freeze() == other.freeze()
+    }
+}
+
+impl<M> Eq for Coefficient<M> where M: Modulus {}
+
+impl<M> Coefficient<M>
+where
+    M: Modulus,
+{
+    pub const Q: u16 = M::Q;
+    pub(super) const Q_INV: u16 = {
+        check::<M>();
+        (negated_inverse(M::Q) & ((1 << Self::R_LOG) - 1)) as u16
+    };
+    pub const R_LOG: u32 = 18;
+    pub(super) const BARRETT_LOG: u32 = 26;
+    pub(super) const BARRETT: u32 = (1 << Self::BARRETT_LOG) / (Self::Q as u32);
+    // `2 ^ 32 / q`, the quotient is the high half of the 64 bit product
+    pub(super) const BARRETT_WIDE: u32 = ((1 << 32) / (Self::Q as u64)) as u32;
+    // the multiple of `q` added before the subtraction, more than `2 ^ 16`,
+    // so more than any coefficient, `6 * q` for NewHope
+    pub(super) const BIAS: u32 = ((1 << 16) / (Self::Q as u32) + 1) * (Self::Q as u32);
 
-    pub const MIDDLE: Self = Coefficient(Self::Q / 2);
+    // the width of the packed coefficient
+    pub(super) const WIDTH: usize = M::Bits::USIZE;
 
-    pub const QUARTER: Self = Coefficient(Self::Q / 4);
+    pub const MIDDLE: Self = Coefficient(M::Q / 2, PhantomData);
+
+    // `x / q` for `x < 2 ^ 18`, without division and branching
+    fn quotient(x: u32) -> u32 {
+        let q = Self::Q as u32;
+        let t = (x * Self::BARRETT) >> Self::BARRETT_LOG;
+        // the estimation is less by at most one, so `r < 2 * q`
+        let r = x - t * q;
+        t + ((q - 1).wrapping_sub(r) >> 31)
+    }
+
+    // `x % q` for `x < 2 ^ 18`, without division and branching
+    pub fn reduce(x: u32) -> u16 {
+        (x - Self::quotient(x) * (Self::Q as u32)) as u16
+    }
+
+    // `x / q` for any `x`, without division and branching
+    fn quotient_wide(x: u32) -> u32 {
+        let q = Self::Q as u32;
+        let t = (((x as u64) * (Self::BARRETT_WIDE as u64)) >> 32) as u32;
+        // the estimation is less by at most one, so `r < 2 * q`
+        let r = x - t * q;
+        t + ((q - 1).wrapping_sub(r) >> 31)
+    }
+
+    // `x % q` for any `x`, so the product of two coefficients needs one reduction
+    pub fn reduce_wide(x: u32) -> u16 {
+        (x - Self::quotient_wide(x) * (Self::Q as u32)) as u16
+    }
+
+    // `a - b` plus a multiple of `q`, positive for any `b` and less than `2 ^ 18`
+    pub fn lazy_sub(&self, other: &Self) -> u32 {
+        self.data() + Self::BIAS - other.data()
+    }
 
     pub fn freeze(&self) -> i16 {
-        let r = (self.0 % Self::Q) as i16;
-        let m = r - Self::Q as i16;
-        let c = m >> 15;
-        m ^ ((r ^ m) & c)
+        Self::reduce(self.0 as u32) as i16
     }
 
     pub fn new(r: u16) -> Self {
-        Coefficient(r)
+        Coefficient(r, PhantomData)
     }
 
-    pub fn valid_new(r: u16) -> Result<Self, ()> {
+    pub fn valid_new(r: u16) -> Result<Self, Error> {
         if r < Self::Q {
             Ok(Self::new(r))
         } else {
-            Err(())
+            Err(Error::InvalidCoefficient {
+                offset: 0,
+                index: 0,
+                value: r,
+            })
         }
     }
 
     pub fn try_new(r: u16) -> Option<Self> {
         if r < (core::u16::MAX / Self::Q) * Self::Q {
-            Some(Self::new(r))
+            Some(Self::new(Self::reduce(r as u32)))
         } else {
             None
         }
@@ -55,114 +145,323 @@ impl Coefficient {
         Self::new(((Self::Q as i16) + (s as i16)) as u16)
     }
 
+    // absolute value of the centered representative, branch free
+    pub fn abs(&self) -> u16 {
+        let r = self.freeze();
+        let c = ((Self::Q / 2) as i16 - r) >> 15;
+        let r = r - (c & (Self::Q as i16));
+        let m = r >> 15;
+        ((r + m) ^ m) as u16
+    }
+
     pub fn flip_abs(&self) -> u16 {
         let r = self.freeze() - ((Self::Q / 2) as i16);
         let m = r >> 15;
         ((r + m) ^ m) as u16
     }
 
+    // `x * 2 ^ -18 % q` for `x < q * 2 ^ 18`, the result is less than `2 * q`
     pub fn montgomery_reduce(x: u32) -> Self {
         use core::num::Wrapping;
 
+        let mask = (1 << Self::R_LOG) - 1;
         let Wrapping(u) = Wrapping(x) * Wrapping(Self::Q_INV as u32);
-        let u = (u & ((1 << Self::R_LOG) - 1)) * (Self::Q as u32);
-        Self::new(((x + u) >> Self::R_LOG) as u16)
+        let u = (u & mask) * (Self::Q as u32);
+        // `x + u` may not fit, but its lower bits are zero, add the lower bits first
+        Self::new(((x >> Self::R_LOG) + (((x & mask) + u) >> Self::R_LOG)) as u16)
+    }
+
+    // `floor(w * 2 ^ 16 / q)` for the reduced `w`, the precomputed factor of Shoup
+    pub fn shoup(&self) -> u16 {
+        (((self.freeze() as u32) << 16) / (Self::Q as u32)) as u16
+    }
+
+    // `x * w % q` for the reduced `w` and its factor, the estimation of the quotient
+    // is less by at most one, so the single correction gives the result less than `q`
+    pub fn mul_shoup(&self, w: &Self, w_shoup: u16) -> Self {
+        let q = Self::Q as u32;
+        let t = (self.data() * (w_shoup as u32)) >> 16;
+        let r = self.data() * w.data() - t * q;
+        Self::new((r - (q & ((q - 1).wrapping_sub(r) >> 31).wrapping_neg())) as u16)
     }
 
-    pub fn compress(&self) -> u8 {
+    // rounds `x * 2 ^ bits / q`, the shifted coefficient may exceed `2 ^ 18`
+    pub fn compress(&self, bits: u32) -> u8 {
         let x = self.freeze() as u32;
-        let x = ((x << 3) + ((Self::Q / 2) as u32)) / (Self::Q as u32);
-        (x & 0x07) as u8
+        let x = Self::quotient_wide((x << bits) + ((Self::Q / 2) as u32));
+        (x & ((1 << bits) - 1)) as u8
     }
 
-    pub fn decompress(t: u8) -> Self {
-        Self::new((((t as u32) * (Self::Q as u32) + 4) >> 3) as u16)
+    pub fn decompress(t: u8, bits: u32) -> Self {
+        Self::new((((t as u32) * (Self::Q as u32) + (1 << (bits - 1))) >> bits) as u16)
     }
 
     pub fn data(&self) -> u32 {
         self.0 as u32
     }
+
+    #[cfg(feature = "zeroize")]
+    pub fn zeroize(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0)
+    }
 }
 
-impl<'a, 'b> Add<&'b Coefficient> for &'a Coefficient {
-    type Output = Coefficient;
+impl<'a, 'b, M> Add<&'b Coefficient<M>> for &'a Coefficient<M>
+where
+    M: Modulus,
+{
+    type Output = Coefficient<M>;
 
-    fn add(self, other: &'b Coefficient) -> Self::Output {
-        Coefficient::new((self.0 + other.0) % Coefficient::Q)
+    fn add(self, other: &'b Coefficient<M>) -> Self::Output {
+        Coefficient::new(Coefficient::<M>::reduce(self.data() + other.data()))
     }
 }
 
-impl<'a, 'b> Sub<&'b Coefficient> for &'a Coefficient {
-    type Output = Coefficient;
+impl<'a, 'b, M> Sub<&'b Coefficient<M>> for &'a Coefficient<M>
+where
+    M: Modulus,
+{
+    type Output = Coefficient<M>;
 
-    fn sub(self, other: &'b Coefficient) -> Self::Output {
-        Coefficient::new((self.0 + 3 * Coefficient::Q - other.0) % Coefficient::Q)
+    fn sub(self, other: &'b Coefficient<M>) -> Self::Output {
+        Coefficient::new(Coefficient::<M>::reduce(self.lazy_sub(other)))
     }
 }
 
-impl<'a, 'b> Mul<&'b Coefficient> for &'a Coefficient {
-    type Output = Coefficient;
+impl<'a, 'b, M> Mul<&'b Coefficient<M>> for &'a Coefficient<M>
+where
+    M: Modulus,
+{
+    type Output = Coefficient<M>;
 
-    fn mul(self, other: &'b Coefficient) -> Self::Output {
-        let t = Coefficient::montgomery_reduce(3186 * other.data());
-        Coefficient::montgomery_reduce(t.data() * self.data())
+    fn mul(self, other: &'b Coefficient<M>) -> Self::Output {
+        Coefficient::new(Coefficient::<M>::reduce_wide(self.data() * other.data()))
     }
 }
 
-pub struct CoefficientRich<S>(pub Coefficient, pub PhantomData<S>)
+// Not reduced at all, the functor reduces the value once in the end. The product
+// of two coefficients is less than `2 ^ 30`, the subtraction adds the multiple
+// of `q` more than that, `q * 2 ^ 17` for NewHope, so an expression like `e + a * s`
+// or `b - a * s` fits. Overflow is caught by the arithmetic checks of the debug build.
+pub struct CoefficientRich<S, M = Q12289>(pub u32, pub PhantomData<(S, M)>)
 where
-    S: PolyState;
+    S: PolyState,
+    M: Modulus;
 
-impl<S> CoefficientRich<S>
+impl<S, M> CoefficientRich<S, M>
 where
     S: PolyState,
+    M: Modulus,
 {
+    // `q` shifted to the highest bit but one, between `2 ^ 30` and `2 ^ 31`
+    const BIAS: u32 = (M::Q as u32) << ((M::Q as u32).leading_zeros() - 1);
+
     pub fn new(raw: u32) -> Self {
-        CoefficientRich(Coefficient::new(raw as u16), PhantomData)
+        CoefficientRich(raw, PhantomData)
+    }
+
+    pub fn reduce(self) -> Coefficient<M> {
+        Coefficient::new(Coefficient::<M>::reduce_wide(self.0))
     }
 }
 
-impl<O, L, R, D> Add<CoefficientRich<(O, R, D)>> for CoefficientRich<(O, L, D)>
+impl<O, L, R, D, M> Add<CoefficientRich<(O, R, D), M>> for CoefficientRich<(O, L, D), M>
 where
-    O: Involution,
-    L: Involution + BitOr<R>,
-    <L as BitOr<R>>::Output: Involution,
-    R: Involution,
-    D: Involution,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+    M: Modulus,
 {
-    type Output = CoefficientRich<(O, <L as BitOr<R>>::Output, D)>;
+    type Output = CoefficientRich<(O, L::Or<R>, D), M>;
 
-    fn add(self, other: CoefficientRich<(O, R, D)>) -> Self::Output {
-        CoefficientRich::new((&self.0 + &other.0).data())
+    fn add(self, other: CoefficientRich<(O, R, D), M>) -> Self::Output {
+        CoefficientRich::new(self.0 + other.0)
     }
 }
 
-impl<O, L, R, D> Sub<CoefficientRich<(O, R, D)>> for CoefficientRich<(O, L, D)>
+impl<O, L, R, D, M> Sub<CoefficientRich<(O, R, D), M>> for CoefficientRich<(O, L, D), M>
 where
-    O: Involution,
-    L: Involution + BitOr<R>,
-    <L as BitOr<R>>::Output: Involution,
-    R: Involution,
-    D: Involution,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+    M: Modulus,
 {
-    type Output = CoefficientRich<(O, <L as BitOr<R>>::Output, D)>;
+    type Output = CoefficientRich<(O, L::Or<R>, D), M>;
 
-    fn sub(self, other: CoefficientRich<(O, R, D)>) -> Self::Output {
-        CoefficientRich::new((&self.0 - &other.0).data())
+    fn sub(self, other: CoefficientRich<(O, R, D), M>) -> Self::Output {
+        CoefficientRich::new(self.0 + Self::BIAS - other.0)
     }
 }
 
-impl<O, L, R, D> Mul<CoefficientRich<(O, R, D)>> for CoefficientRich<(O, L, D)>
+impl<O, L, R, D, M> Mul<CoefficientRich<(O, R, D), M>> for CoefficientRich<(O, L, D), M>
 where
-    O: Involution,
-    L: Involution + BitAnd<R>,
-    <L as BitAnd<R>>::Output: Involution,
-    R: Involution,
-    D: Involution,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+    M: Modulus,
 {
-    type Output = CoefficientRich<(O, <L as BitAnd<R>>::Output, D)>;
+    type Output = CoefficientRich<(O, L::And<R>, D), M>;
+
+    fn mul(self, other: CoefficientRich<(O, R, D), M>) -> Self::Output {
+        CoefficientRich::new(self.0 * other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coefficient, CoefficientRich};
+    use crate::poly::{Natural, Small, Time, Modulus, Q12289, Q7681};
+
+    // the values next to the multiples of `q` and to the bounds
+    fn edges<M>() -> impl Iterator<Item = u32>
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        (0..=5)
+            .flat_map(move |k| [k * q, k * q + 1, (k * q).saturating_sub(1)].to_vec())
+            .chain([(1 << 15) - 1, (1 << 16) - 1].to_vec())
+    }
+
+    // every check runs for NewHope and for the other modulus
+    macro_rules! moduli {
+        ($($name:ident => $check:ident),* $(,)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    $check::<Q12289>();
+                    $check::<Q7681>();
+                }
+            )*
+        };
+    }
+
+    moduli! {
+        reduction => check_reduction,
+        reduction_wide => check_reduction_wide,
+        arithmetic => check_arithmetic,
+        montgomery => check_montgomery,
+        shoup => check_shoup,
+        abs => check_abs,
+        compression => check_compression,
+    }
 
-    fn mul(self, other: CoefficientRich<(O, R, D)>) -> Self::Output {
-        CoefficientRich::new((&self.0 * &other.0).data())
+    fn check_reduction<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        for x in 0..(1 << 18) {
+            assert_eq!(Coefficient::<M>::quotient(x), x / q);
+            assert_eq!(Coefficient::<M>::reduce(x) as u32, x % q);
+        }
+    }
+
+    fn check_reduction_wide<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        for k in 0..=(u32::MAX / q) {
+            for &x in &[k * q, k * q + 1, (k * q).saturating_sub(1), k * q + q / 2] {
+                assert_eq!(Coefficient::<M>::reduce_wide(x) as u32, x % q);
+            }
+        }
+        for _ in 0..0x10000 {
+            let x = rand::random::<u32>();
+            assert_eq!(Coefficient::<M>::reduce_wide(x) as u32, x % q);
+        }
+        assert_eq!(Coefficient::<M>::reduce_wide(u32::MAX) as u32, u32::MAX % q);
+    }
+
+    fn check_arithmetic<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        for a in edges::<M>() {
+            for b in edges::<M>() {
+                let (x, y) = (Coefficient::<M>::new(a as u16), Coefficient::new(b as u16));
+                assert_eq!((&x + &y).data(), (a + b) % q);
+                assert_eq!((&x - &y).data(), (a + (b / q + 1) * q - b) % q);
+                let expected = (a as u64) * (b as u64) % (q as u64);
+                assert_eq!((&x * &y).data(), expected as u32);
+                if a < 1 << 15 && b < 1 << 15 {
+                    let rich = CoefficientRich::<(Natural, Small, Time), M>::new;
+                    let r = rich(a) - rich(a) * rich(b) + rich(b);
+                    let expected = (a as i64) - (a as i64) * (b as i64) + (b as i64);
+                    assert_eq!(r.reduce().data() as i64, expected.rem_euclid(q as i64));
+                }
+            }
+        }
+    }
+
+    fn check_montgomery<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u64;
+        let r_log = Coefficient::<M>::R_LOG;
+        // `2 ^ -18 % q`
+        let r_inv = (1..q).find(|r| (r << r_log) % q == 1).unwrap();
+        let bound = (q as u32) << r_log;
+        let xs = (0..0x10000)
+            .map(|_| rand::random::<u32>() % bound)
+            .chain((0..=(1 << 10)).map(|k| (bound - 1) - k * (q as u32)));
+        for x in xs {
+            let r = Coefficient::<M>::montgomery_reduce(x).data();
+            assert!(r < 2 * (q as u32));
+            assert_eq!((r as u64) % q, (x as u64) * r_inv % q);
+        }
+    }
+
+    fn check_shoup<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        let ws = (0..q).step_by(97).chain([1, q / 2, q - 1].to_vec());
+        for w in ws {
+            let w = Coefficient::<M>::new(w as u16);
+            let w_shoup = w.shoup();
+            for x in edges::<M>().filter(|&x| x < 1 << 16) {
+                let x = Coefficient::new(x as u16);
+                assert_eq!(x.mul_shoup(&w, w_shoup).data(), (&x * &w).data());
+            }
+        }
+    }
+
+    fn check_abs<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q;
+        for x in 0..q {
+            let expected = if x > q / 2 { q - x } else { x };
+            assert_eq!(Coefficient::<M>::new(x).abs(), expected);
+        }
+    }
+
+    fn check_compression<M>()
+    where
+        M: Modulus,
+    {
+        let q = M::Q as u32;
+        for bits in 1..=8 {
+            let mask = (1 << bits) - 1;
+            for x in 0..=(u16::MAX as u32) {
+                let c = Coefficient::<M>::new(x as u16);
+                let expected = ((((x % q) << bits) + q / 2) / q) & mask;
+                assert_eq!(c.compress(bits) as u32, expected);
+            }
+            for t in 0..=mask {
+                let c = Coefficient::<M>::decompress(t as u8, bits);
+                assert!(c.data() < q);
+                assert_eq!(c.compress(bits) as u32, t);
+            }
+        }
     }
 }
@@ -0,0 +1,77 @@
+use super::kernel;
+
+pub const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// the rotation and the destination of each lane in the combined rho and pi steps
+pub const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+pub const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Four independent Keccak-f[1600] states, interleaved by lane,
+/// so the permutation runs on all of them at once.
+#[derive(Default)]
+pub struct KeccakX4 {
+    state: [[u64; 4]; 25],
+}
+
+impl KeccakX4 {
+    /// Xors the bytes into the state of the `instance` starting at the byte `offset`.
+    pub fn xor(&mut self, instance: usize, offset: usize, bytes: &[u8]) {
+        for (i, b) in bytes.iter().enumerate() {
+            let position = offset + i;
+            self.state[position / 8][instance] ^= (*b as u64) << (8 * (position % 8));
+        }
+    }
+
+    /// Reads the bytes of the state of the `instance` starting at the byte `offset`.
+    pub fn read(&self, instance: usize, offset: usize, buffer: &mut [u8]) {
+        for (i, b) in buffer.iter_mut().enumerate() {
+            let position = offset + i;
+            *b = (self.state[position / 8][instance] >> (8 * (position % 8))) as u8;
+        }
+    }
+
+    /// Zeroes the state in place.
+    pub fn clear(&mut self) {
+        self.state = [[0; 4]; 25];
+    }
+
+    pub fn permute(&mut self) {
+        kernel::f1600x4(&mut self.state)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for KeccakX4 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.state)
+    }
+}
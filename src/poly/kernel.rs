@@ -0,0 +1,143 @@
+// selects the kernel at runtime, the vectorized one needs `std` to detect the cpu
+
+use super::{coefficient::Coefficient, modulus::Modulus, portable};
+use crate::Error;
+
+macro_rules! dispatch {
+    ($name:ident($($arg:expr),*)) => {
+        dispatch!($name($($arg),*) if true)
+    };
+    ($name:ident($($arg:expr),*) if $condition:expr) => {{
+        #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            if $condition && vectorized() && std::is_x86_feature_detected!("avx2") {
+                // safe, the cpu supports avx2
+                #[allow(unsafe_code)]
+                return unsafe { super::avx2::$name($($arg),*) };
+            }
+        }
+        portable::$name($($arg),*)
+    }};
+}
+
+#[cfg(all(
+    test,
+    feature = "std",
+    feature = "low-memory",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+std::thread_local! {
+    static PORTABLE: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+}
+
+#[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+fn vectorized() -> bool {
+    #[cfg(all(test, feature = "low-memory"))]
+    return !PORTABLE.with(|portable| portable.get());
+    #[cfg(not(all(test, feature = "low-memory")))]
+    true
+}
+
+/// Runs `f` with the portable kernels in this thread, even if the cpu has avx2.
+#[cfg(all(test, feature = "low-memory"))]
+pub fn with_portable<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        PORTABLE.with(|portable| portable.set(true));
+        let r = f();
+        PORTABLE.with(|portable| portable.set(false));
+        r
+    }
+    #[cfg(not(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64"))))]
+    f()
+}
+
+pub fn multiply<M>(c: &mut [Coefficient<M>], gammas: &[u16])
+where
+    M: Modulus,
+{
+    dispatch!(multiply(c, gammas))
+}
+
+pub fn transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    dispatch!(transform(c, omegas))
+}
+
+pub fn inverse_transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    dispatch!(inverse_transform(c, omegas))
+}
+
+pub fn add<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    dispatch!(add(r, a, b))
+}
+
+pub fn sub<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    dispatch!(sub(r, a, b))
+}
+
+pub fn mul<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    dispatch!(mul(r, a, b))
+}
+
+pub fn mul_shoup<M>(
+    r: &mut [Coefficient<M>],
+    a: &[Coefficient<M>],
+    w: &[Coefficient<M>],
+    w_shoup: &[u16],
+) where
+    M: Modulus,
+{
+    dispatch!(mul_shoup(r, a, w, w_shoup))
+}
+
+pub fn pack<M>(c: &[Coefficient<M>], r: &mut [u8])
+where
+    M: Modulus,
+{
+    // the vectorized codec is for the width of 14 bits only
+    dispatch!(pack(c, r) if Coefficient::<M>::WIDTH == 14)
+}
+
+pub fn unpack<M>(v: &[u8], c: &mut [Coefficient<M>]) -> Result<(), Error>
+where
+    M: Modulus,
+{
+    dispatch!(unpack(v, c) if Coefficient::<M>::WIDTH == 14)
+}
+
+pub fn compress<M>(c: &[Coefficient<M>], a: &mut [u8], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    // the vectorized compression is for 3 bits only
+    dispatch!(compress(c, a, bits, reversed) if bits == 3)
+}
+
+pub fn decompress<M>(v: &[u8], c: &mut [Coefficient<M>], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    dispatch!(decompress(v, c, bits, reversed) if bits == 3)
+}
+
+pub fn f1600x4(state: &mut [[u64; 4]; 25]) {
+    dispatch!(f1600x4(state))
+}
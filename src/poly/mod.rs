@@ -1,5 +1,43 @@
+mod modulus;
+mod noise;
+mod params;
 mod coefficient;
+mod state;
 mod tables;
+mod portable;
+#[cfg(all(
+    any(test, feature = "std"),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[allow(unsafe_code)]
+mod avx2;
+mod kernel;
+mod keccak;
+mod xof;
 mod poly;
+#[cfg(any(test, feature = "low-memory"))]
+mod stream;
 
-pub use self::poly::{Poly, PolyState, PolySize, FromSeed, FromSeedSmall, ReverseBits, Ntt};
+pub use self::state::{
+    PolyState, BitOrder, Size, Domain, Natural, Reversed, Small, Uniform, Time, Frequency,
+};
+pub use self::poly::{
+    Poly, Precomputed, Ring, PolySize, Encoding, FromSeed, FromSeedSmall, ReverseBits, Ntt, Margin,
+};
+pub use self::modulus::{Modulus, Q12289};
+#[cfg(test)]
+pub(crate) use self::modulus::Q7681;
+pub use self::noise::Distribution;
+pub use self::params::{ParameterSet, NewHope};
+#[cfg(test)]
+pub(crate) use self::params::{Toy, Gaussian};
+pub use self::tables::Tables;
+pub use self::xof::{Xof, Shake128X4, Shake256X4};
+#[cfg(feature = "90s")]
+pub use self::xof::Aes256CtrX4;
+#[cfg(feature = "low-memory")]
+pub(crate) use self::stream::{Scratch, View};
+#[cfg(all(test, feature = "low-memory"))]
+pub(crate) use self::kernel::with_portable;
+#[cfg(feature = "std")]
+pub(crate) use self::{coefficient::Coefficient, poly::threshold};
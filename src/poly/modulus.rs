@@ -0,0 +1,91 @@
+// The prime modulus of the coefficients. The reductions, the twiddle factors
+// and the width of the encoding are derived from it at compile time.
+
+use rac::generic_array::typenum::{Unsigned, U14};
+#[cfg(test)]
+use rac::generic_array::typenum::U13;
+
+/// The prime modulus `q`.
+///
+/// The coefficients are lazily reduced, they stay less than `2 ^ 15`, and
+/// the Montgomery reduction works modulo `2 ^ 18`, so `q` must be between
+/// `2 ^ 12` and `2 ^ 14`. The ring of size `N` needs `2 * N` to divide `q - 1`,
+/// otherwise its transform does not exist and the tables fail to compile.
+pub trait Modulus
+where
+    Self: Clone,
+{
+    const Q: u16;
+
+    /// The width of the packed coefficient, the bit length of `q - 1`.
+    type Bits: Unsigned;
+
+    /// The primitive `2 * N`-th roots of unity the transforms of the rings
+    /// of size 256, 512, 1024 and 2048 use. The zero means the smallest root.
+    const GAMMAS: [u16; 4] = [0; 4];
+}
+
+/// The modulus of NewHope, the roots of the rings of size 512 and 1024
+/// are the ones of the specification.
+#[derive(Clone)]
+pub struct Q12289;
+
+impl Modulus for Q12289 {
+    const Q: u16 = 12289;
+
+    type Bits = U14;
+
+    const GAMMAS: [u16; 4] = [3, 10968, 7, 41];
+}
+
+// the modulus of the first round of Kyber, only the ring of size 256 has
+// the transform, the tests run the whole scheme with it, see `Toy`
+#[cfg(test)]
+#[derive(Clone)]
+pub struct Q7681;
+
+#[cfg(test)]
+impl Modulus for Q7681 {
+    const Q: u16 = 7681;
+
+    type Bits = U13;
+}
+
+// `-q ^ -1 mod 2 ^ 32` by the iteration of Newton, each step doubles
+// the number of the correct bits, `q` itself is correct in three bits
+pub(super) const fn negated_inverse(q: u16) -> u32 {
+    let q = q as u32;
+    let mut r = q;
+    let mut i = 0;
+    while i < 4 {
+        r = r.wrapping_mul(2u32.wrapping_sub(q.wrapping_mul(r)));
+        i += 1;
+    }
+    r.wrapping_neg()
+}
+
+// fails the compilation if the modulus does not meet the requirements
+pub(super) const fn check<M>()
+where
+    M: Modulus,
+{
+    let q = M::Q as u32;
+    assert!(q > 1 << 12 && q < 1 << 14, "the modulus is out of range");
+    assert!(q & 1 == 1, "the modulus must be odd");
+    let bits = M::Bits::U32;
+    assert!((q - 1) >> (bits - 1) == 1, "the width is wrong");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Modulus, Q12289, negated_inverse};
+
+    #[test]
+    fn inverse() {
+        for q in (((1 << 12) + 1)..(1 << 14)).step_by(2) {
+            let r = negated_inverse(q);
+            assert_eq!((q as u32).wrapping_mul(r), u32::MAX);
+        }
+        assert_eq!(negated_inverse(Q12289::Q) & ((1 << 18) - 1), 12287);
+    }
+}
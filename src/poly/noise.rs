@@ -0,0 +1,222 @@
+// The distributions of the noise. Each coefficient takes the same number of bits
+// of the output of the extendable output function, and the sample does not branch
+// on them, so the sampling is constant time. The table of the discrete Gaussian
+// is computed at compile time, `core` has no exponent, so it is here.
+
+/// The distribution of the coefficients of the noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    /// The centered binomial distribution, the difference of the Hamming weights
+    /// of two strings of `k` bits, `k` is from 1 to 8. Takes `2 * k` bits.
+    Binomial(u32),
+    /// The discrete Gaussian distribution of the deviation `sigma` cut at
+    /// `tail * sigma`, the cut is at most 127. Sampled by the table of the
+    /// cumulative distribution of 63 bits precision, takes 64 bits.
+    Gaussian { sigma: f64, tail: f64 },
+}
+
+impl Distribution {
+    /// The bound of the absolute value of the noise.
+    pub const fn bound(&self) -> u32 {
+        match *self {
+            Distribution::Binomial(k) => k,
+            Distribution::Gaussian { sigma, tail } => {
+                let cut = tail * sigma;
+                let bound = cut as u32;
+                if (bound as f64) < cut {
+                    bound + 1
+                } else {
+                    bound
+                }
+            },
+        }
+    }
+
+    // the bits of the output per coefficient
+    pub(super) const fn bits(&self) -> u32 {
+        match *self {
+            Distribution::Binomial(k) => 2 * k,
+            Distribution::Gaussian { .. } => 64,
+        }
+    }
+
+    // fails the compilation if the distribution is out of range
+    pub(super) const fn check(&self) {
+        match *self {
+            Distribution::Binomial(k) => assert!(k >= 1 && k <= 8, "the noise is out of range"),
+            Distribution::Gaussian { sigma, tail } => {
+                assert!(sigma > 0.0 && tail > 0.0, "the noise is out of range");
+                assert!(self.bound() < TABLE_LENGTH as u32, "the cut is too wide");
+            },
+        }
+    }
+
+    // the probabilities of `-bound..=bound` the sampler gives, the Gaussian
+    // is the one of the table, not the ideal one
+    #[cfg(feature = "std")]
+    pub(crate) fn masses(&self) -> std::vec::Vec<f64> {
+        let bound = self.bound() as usize;
+        let mut masses = std::vec![0.0; 2 * bound + 1];
+        match *self {
+            Distribution::Binomial(k) => {
+                // `C(2 * k, k + x) / 2 ^ (2 * k)`
+                let mut c = 1.0;
+                for (i, m) in masses.iter_mut().enumerate() {
+                    *m = c / ((1u64 << (2 * k)) as f64);
+                    c = c * ((2 * bound - i) as f64) / ((i + 1) as f64);
+                }
+            },
+            Distribution::Gaussian { .. } => {
+                // the magnitude is `i` with `table[i - 1] - table[i]`, the sign halves it
+                let t = table(*self);
+                let one = (1u64 << 63) as f64;
+                masses[bound] = 1.0 - (t[0] as f64) / one;
+                for i in 1..=bound {
+                    let m = ((t[i - 1] - t[i]) as f64) / one / 2.0;
+                    masses[bound + i] = m;
+                    masses[bound - i] = m;
+                }
+            },
+        }
+        masses
+    }
+
+    // the noise coefficient from the `bits` of the output, the table is `table(self)`
+    pub(super) fn sample(&self, table: &[u64], x: u64) -> i8 {
+        match *self {
+            Distribution::Binomial(k) => {
+                let mask = (1 << k) - 1;
+                let a = (x & mask).count_ones() as i8;
+                let b = ((x >> k) & mask).count_ones() as i8;
+                a - b
+            },
+            Distribution::Gaussian { .. } => {
+                // the magnitude is the number of the entries greater than
+                // the uniform `r`, the top bit is the sign
+                let r = x & (u64::MAX >> 1);
+                let sign = x >> 63;
+                let m = table.iter().fold(0, |m, t| m + (r.wrapping_sub(*t) >> 63));
+                ((m ^ sign.wrapping_neg()).wrapping_add(sign)) as i8
+            },
+        }
+    }
+}
+
+// the cut is less than the length
+const TABLE_LENGTH: usize = 128;
+
+// the entry `i` is `2 ^ 63` times the probability that the absolute value
+// exceeds `i`, the entries past the bound are zero, empty for the binomial
+pub(super) const fn table(d: Distribution) -> [u64; TABLE_LENGTH] {
+    let mut table = [0; TABLE_LENGTH];
+    if let Distribution::Gaussian { sigma, .. } = d {
+        let bound = d.bound() as usize;
+
+        // the weight of the absolute value, both signs but the zero
+        let mut weights = [0.0; TABLE_LENGTH];
+        let mut total = 0.0;
+        let mut i = 0;
+        while i <= bound {
+            let w = 1.0 / exp(((i * i) as f64) / (2.0 * sigma * sigma));
+            weights[i] = if i == 0 { w } else { 2.0 * w };
+            total += weights[i];
+            i += 1;
+        }
+
+        // sums from the cut, so the small probabilities keep the precision
+        let mut sum = 0.0;
+        let mut i = bound;
+        while i > 0 {
+            sum += weights[i];
+            table[i - 1] = (sum / total * ((1u64 << 63) as f64)) as u64;
+            i -= 1;
+        }
+    }
+    table
+}
+
+// `e ^ x` for `x >= 0`, the series of `e ^ (x / 2 ^ k)` squared `k` times
+const fn exp(x: f64) -> f64 {
+    let (mut y, mut k) = (x, 0);
+    while y > 0.5 {
+        y /= 2.0;
+        k += 1;
+    }
+    let (mut sum, mut term, mut i) = (1.0, 1.0, 1);
+    while i < 24 {
+        term *= y / (i as f64);
+        sum += term;
+        i += 1;
+    }
+    while k > 0 {
+        sum *= sum;
+        k -= 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Distribution, exp, table};
+
+    #[test]
+    fn exponent() {
+        let expected = [
+            (0.0, 1.0),
+            (1.0, core::f64::consts::E),
+            (10.0, 22026.465794806718),
+            (72.0, 1.8586717452841279e31),
+        ];
+        for (x, e) in expected {
+            assert!((exp(x) / e - 1.0).abs() < 1e-12, "{}", x);
+        }
+    }
+
+    #[test]
+    fn gaussian() {
+        let d = Distribution::Gaussian {
+            sigma: 2.0,
+            tail: 12.0,
+        };
+        assert_eq!(d.bound(), 24);
+        let t = table(d);
+        assert!(t[..24].windows(2).all(|w| w[0] >= w[1]));
+        assert!(t[24..].iter().all(|&t| t == 0));
+        // `P(|x| > 0)` is `1 - 1 / sum(exp(-i ^ 2 / 8))`, about 0.80
+        let p = (t[0] as f64) / ((1u64 << 63) as f64);
+        assert!((p - 0.8005).abs() < 1e-3);
+
+        // the extremes of the uniform and the sign, the tail past the
+        // precision of the table is never sampled
+        let table = &t[..24];
+        let max = table.iter().filter(|&&t| t != 0).count() as i8;
+        assert_eq!(max, 18);
+        assert_eq!(d.sample(table, 0), max);
+        assert_eq!(d.sample(table, 1 << 63), -max);
+        assert_eq!(d.sample(table, u64::MAX >> 1), 0);
+        assert_eq!(d.sample(table, u64::MAX), 0);
+
+        // the moments of the samples of the uniform bits
+        let count = 1 << 16;
+        let (mut sum, mut square) = (0i64, 0i64);
+        for _ in 0..count {
+            let x = d.sample(table, rand::random()) as i64;
+            assert!(x.abs() <= 24);
+            sum += x;
+            square += x * x;
+        }
+        let mean = (sum as f64) / (count as f64);
+        let variance = (square as f64) / (count as f64) - mean * mean;
+        assert!(mean.abs() < 0.1);
+        assert!((variance - 4.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn binomial() {
+        let d = Distribution::Binomial(8);
+        assert_eq!((d.bound(), d.bits()), (8, 16));
+        assert_eq!(d.sample(&[], 0x00ff), 8);
+        assert_eq!(d.sample(&[], 0xff00), -8);
+        assert_eq!(d.sample(&[], 0x0f0f), 0);
+    }
+}
@@ -0,0 +1,81 @@
+// The parameter set of the scheme, everything but the size of the ring. The noise
+// sampler, the compression and the length of the cipher text are derived from it.
+
+use super::{
+    modulus::{Modulus, Q12289},
+    noise::Distribution,
+};
+#[cfg(test)]
+use super::modulus::Q7681;
+use rac::generic_array::typenum::{Unsigned, U3};
+#[cfg(test)]
+use rac::generic_array::typenum::U4;
+
+/// The modulus, the noise and the compression of the cipher text.
+pub trait ParameterSet
+where
+    Self: Clone,
+{
+    type Modulus: Modulus;
+
+    /// The distribution of the noise, of the secret and of the errors.
+    const NOISE: Distribution;
+
+    /// The bits per coefficient of the compressed polynomial of the cipher text,
+    /// from 1 to 8.
+    type CompressionBits: Unsigned;
+}
+
+/// The parameters of the specification.
+#[derive(Clone)]
+pub struct NewHope;
+
+impl ParameterSet for NewHope {
+    type Modulus = Q12289;
+
+    const NOISE: Distribution = Distribution::Binomial(8);
+
+    type CompressionBits = U3;
+}
+
+// the smaller modulus, noise and the wider compression, the tests run
+// the whole scheme with it
+#[cfg(test)]
+#[derive(Clone)]
+pub struct Toy;
+
+#[cfg(test)]
+impl ParameterSet for Toy {
+    type Modulus = Q7681;
+
+    const NOISE: Distribution = Distribution::Binomial(4);
+
+    type CompressionBits = U4;
+}
+
+// the discrete Gaussian of the same variance as the noise of NewHope
+#[cfg(test)]
+#[derive(Clone)]
+pub struct Gaussian;
+
+#[cfg(test)]
+impl ParameterSet for Gaussian {
+    type Modulus = Q12289;
+
+    const NOISE: Distribution = Distribution::Gaussian {
+        sigma: 2.0,
+        tail: 12.0,
+    };
+
+    type CompressionBits = U3;
+}
+
+// fails the compilation if the parameters are out of range
+pub(super) const fn check<P>()
+where
+    P: ParameterSet,
+{
+    P::NOISE.check();
+    let bits = P::CompressionBits::U32;
+    assert!(bits >= 1 && bits <= 8, "the compression is out of range");
+}
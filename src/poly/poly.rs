@@ -1,328 +1,785 @@
-use super::coefficient::{Coefficient, CoefficientRich};
+use super::{
+    coefficient::{Coefficient, CoefficientRich},
+    modulus::Modulus,
+    noise::{self, Distribution},
+    params::{self, ParameterSet, NewHope},
+    state::{PolyState, BitOrder, Size, Domain, Small},
+    tables::{Tables, reverse_bits},
+    xof::Xof,
+    kernel,
+};
+use crate::Error;
 use core::{
+    array, fmt,
     marker::PhantomData,
-    ops::{Mul, Div, Not},
+    ops::{Add, Sub, Mul},
 };
 use rac::generic_array::{
     GenericArray, ArrayLength,
-    typenum::{Unsigned, U8, U14, U3, U1024, U32, Bit, B0, PowerOfTwo, Logarithm2},
+    typenum::{
+        Unsigned, Sum, Prod, Quot, U1, U2, U3, U4, U5, U6, U7, U8, U13, U14, U32, U64, U256, U512,
+        U1024, U2048,
+    },
 };
 
-pub trait PolySize {
-    type PolyLength: ArrayLength<Coefficient> + Unsigned;
+/// The ring `Z_q[x] / (x ^ N + 1)`, the polynomials have `N` coefficients.
+pub struct Ring<const N: usize, P = NewHope>(PhantomData<P>)
+where
+    P: ParameterSet;
+
+/// The supported sizes of the ring. The encodings are `GenericArray`, so their lengths
+/// are `typenum` numbers, the lengths of the keys and the cipher texts are here
+/// because `typenum` cannot add the lengths of a generic ring. The lengths depend
+/// on the width of the modulus and the compression of the parameter set.
+pub trait PolySize
+where
+    Self: Tables,
+{
+    /// `N` itself.
+    type PolyLength: Unsigned;
     type PackedLength: ArrayLength<u8>;
     type CompressedLength: ArrayLength<u8>;
+    /// The packed polynomial and the seed of the parameter, the public key.
+    type PublicKeyLength: ArrayLength<u8>;
+    /// The packed polynomial and the compressed one, the cipher text of `Cpa`.
+    type CipherTextLength: ArrayLength<u8>;
+    /// The cipher text of `Cpa` and the confirmation hash, the cipher text of `Cca`.
+    type CipherTextCcaLength: ArrayLength<u8>;
+    /// The secret, the public key, its hash and the secret for the rejection,
+    /// the secret key of `Cca`.
+    type SecretKeyCcaLength: ArrayLength<u8>;
 }
 
-impl<N> PolySize for N
-where
-    N: Div<U8> + PowerOfTwo + ArrayLength<Coefficient> + Unsigned,
-    <N as Div<U8>>::Output: Mul<U14> + Mul<U3>,
-    <<N as Div<U8>>::Output as Mul<U14>>::Output: ArrayLength<u8>,
-    <<N as Div<U8>>::Output as Mul<U3>>::Output: ArrayLength<u8>,
-{
-    type PolyLength = N;
-    type PackedLength = <<N as Div<U8>>::Output as Mul<U14>>::Output;
-    type CompressedLength = <<N as Div<U8>>::Output as Mul<U3>>::Output;
+/// The lengths of the ring of size `L`, the trait is implemented by the pair
+/// of the width of the packed coefficient and the bits of the compressed one.
+/// The modulus is between `2 ^ 12` and `2 ^ 14`, so the width is 13 or 14 bits,
+/// the compression is from 1 to 8 bits.
+pub trait Encoding<L> {
+    type PackedLength: ArrayLength<u8>;
+    type CompressedLength: ArrayLength<u8>;
+    type PublicKeyLength: ArrayLength<u8>;
+    type CipherTextLength: ArrayLength<u8>;
+    type CipherTextCcaLength: ArrayLength<u8>;
+    type SecretKeyCcaLength: ArrayLength<u8>;
 }
 
-pub trait Involution
-where
-    Self: Sized,
-{
-    type Op: Involution<Op = Self>;
+macro_rules! encoding {
+    ($($width:ty),* ; $bits:tt ; $lengths:tt) => {
+        $(encoding!(@width $width ; $bits ; $lengths);)*
+    };
+    (@width $width:ty ; [$($bits:ty),*] ; $lengths:tt) => {
+        $(encoding!(@pair $width, $bits ; $lengths);)*
+    };
+    (@pair $width:ty, $bits:ty ; [$($length:ty),*]) => {
+        $(
+            impl Encoding<$length> for ($width, $bits) {
+                type PackedLength = Prod<Quot<$length, U8>, $width>;
+                type CompressedLength = Prod<Quot<$length, U8>, $bits>;
+                type PublicKeyLength = Sum<Self::PackedLength, U32>;
+                type CipherTextLength = Sum<Self::PackedLength, Self::CompressedLength>;
+                type CipherTextCcaLength = Sum<Self::CipherTextLength, U32>;
+                type SecretKeyCcaLength = Sum<Sum<Self::PackedLength, Self::PublicKeyLength>, U64>;
+            }
+        )*
+    };
 }
 
-impl<T> Involution for T
-where
-    T: Bit + Not,
-    <T as Not>::Output: Bit + Not<Output = T>,
-{
-    type Op = <T as Not>::Output;
+encoding! {
+    U13, U14;
+    [U1, U2, U3, U4, U5, U6, U7, U8];
+    [U256, U512, U1024, U2048]
 }
 
-pub trait PolyState {
-    type BitOrder: Involution;
-    type Size: Involution;
-    type Domain: Involution;
+// the pair the lengths of the parameter set are implemented for
+type Widths<P> = (
+    <<P as ParameterSet>::Modulus as Modulus>::Bits,
+    <P as ParameterSet>::CompressionBits,
+);
+
+macro_rules! ring {
+    ($($n:literal => $length:ty),* $(,)?) => {
+        $(
+            const _: () = assert!(<$length as Unsigned>::USIZE == $n);
+
+            impl<P> PolySize for Ring<$n, P>
+            where
+                P: ParameterSet,
+                Widths<P>: Encoding<$length>,
+            {
+                type PolyLength = $length;
+                type PackedLength = <Widths<P> as Encoding<$length>>::PackedLength;
+                type CompressedLength = <Widths<P> as Encoding<$length>>::CompressedLength;
+                type PublicKeyLength = <Widths<P> as Encoding<$length>>::PublicKeyLength;
+                type CipherTextLength = <Widths<P> as Encoding<$length>>::CipherTextLength;
+                type CipherTextCcaLength = <Widths<P> as Encoding<$length>>::CipherTextCcaLength;
+                type SecretKeyCcaLength = <Widths<P> as Encoding<$length>>::SecretKeyCcaLength;
+            }
+        )*
+    };
 }
 
-impl<BitOrder, Size, Domain> PolyState for (BitOrder, Size, Domain)
+ring! {
+    256 => U256,
+    512 => U512,
+    1024 => U1024,
+    2048 => U2048,
+}
+
+// the arrays of more than 32 elements are not `Default`
+fn zero<T, const N: usize>() -> [T; N]
 where
-    BitOrder: Involution,
-    Size: Involution,
-    Domain: Involution,
+    T: Default,
 {
-    type BitOrder = BitOrder;
-    type Size = Size;
-    type Domain = Domain;
+    array::from_fn(|_| T::default())
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
-pub struct Poly<N, S>
+pub struct Poly<const N: usize, S, P = NewHope>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
     S: PolyState,
 {
-    coefficients: GenericArray<Coefficient, N::PolyLength>,
+    coefficients: [Coefficient<P::Modulus>; N],
     phantom_data: PhantomData<S>,
 }
 
-impl<N, S> Poly<N, S>
+impl<const N: usize, S, P> Poly<N, S, P>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
     S: PolyState,
 {
     const BLOCK_SIZE: usize = 1 << 6;
+    const NOISE: Distribution = {
+        params::check::<P>();
+        P::NOISE
+    };
+    const NOISE_TABLE: &'static [u64] = &noise::table(Self::NOISE);
 
-    pub fn new(coefficients: GenericArray<Coefficient, N::PolyLength>) -> Self {
+    pub fn new(coefficients: [Coefficient<P::Modulus>; N]) -> Self {
         Poly {
             coefficients: coefficients,
             phantom_data: PhantomData,
         }
     }
 
-    pub fn pack(&self) -> GenericArray<u8, N::PackedLength> {
-        let mut r = GenericArray::default();
-
-        for i in 0..(N::PolyLength::USIZE / 4) {
-            let mut t = [0; 4];
-            for j in 0..4 {
-                t[j] = self.coefficients[4 * i + j].freeze() as u16;
-            }
-
-            let r = &mut r[(7 * i)..(7 * (i + 1))];
-            r[0] = (t[0] & 0x00ff) as u8;
-            r[1] = ((t[0] >> 0x8) | (t[1] << 0x6)) as u8;
-            r[2] = (t[1] >> 0x2) as u8;
-            r[3] = ((t[1] >> 0xa) | (t[2] << 0x4)) as u8;
-            r[4] = (t[2] >> 0x4) as u8;
-            r[5] = ((t[2] >> 0xc) | (t[3] << 0x2)) as u8;
-            r[6] = (t[3] >> 0x6) as u8;
-        }
+    // take the coefficients out instead of moving, `Poly` might implement `Drop`
+    fn cast<T>(mut self) -> Poly<N, T, P>
+    where
+        T: PolyState,
+    {
+        Poly::new(core::mem::replace(&mut self.coefficients, zero()))
+    }
 
+    pub fn pack(&self) -> GenericArray<u8, <Ring<N, P> as PolySize>::PackedLength> {
+        let mut r = GenericArray::default();
+        kernel::pack(&self.coefficients, &mut r);
         r
     }
 
-    pub fn unpack(v: &GenericArray<u8, N::PackedLength>) -> Result<Self, ()> {
-        let mut c = GenericArray::default();
-
-        for i in 0..(N::PolyLength::USIZE / 4) {
-            let a = |j| v[7 * i + j] as u16;
-            let c = &mut c[(4 * i)..(4 * (i + 1))];
-
-            c[0] = Coefficient::valid_new((0x00 >> 0x8) | (a(0) << 0x0) | ((a(1) & 0x3f) << 0x8))?;
-            c[1] = Coefficient::valid_new((a(1) >> 0x6) | (a(2) << 0x2) | ((a(3) & 0x0f) << 0xa))?;
-            c[2] = Coefficient::valid_new((a(3) >> 0x4) | (a(4) << 0x4) | ((a(5) & 0x03) << 0xc))?;
-            c[3] = Coefficient::valid_new((a(5) >> 0x2) | (a(6) << 0x6) | ((0x00 & 0x00) << 0xe))?;
-        }
-
+    pub fn unpack(
+        v: &GenericArray<u8, <Ring<N, P> as PolySize>::PackedLength>,
+    ) -> Result<Self, Error> {
+        let mut c = zero();
+        kernel::unpack(v, &mut c)?;
         Ok(Self::new(c))
     }
 
-    pub fn compress(&self) -> GenericArray<u8, N::CompressedLength> {
+    /// The compressed bytes are in natural order, even if the coefficients are not.
+    pub fn compress(&self) -> GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength> {
         let mut a = GenericArray::default();
+        let bits = P::CompressionBits::U32;
+        kernel::compress(&self.coefficients, &mut a, bits, S::BitOrder::REVERSED);
+        a
+    }
 
-        for i in 0..(N::PolyLength::USIZE / 8) {
-            let mut t = [0; 8];
-            for j in 0..8 {
-                t[j] = self.coefficients[8 * i + j].compress()
-            }
+    pub fn decompress(v: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>) -> Self {
+        let mut c = zero();
+        let bits = P::CompressionBits::U32;
+        kernel::decompress(v, &mut c, bits, S::BitOrder::REVERSED);
+        Self::new(c)
+    }
 
-            a[3 * i + 0] = (t[0] >> 0x0) | (t[1] << 0x3) | (t[2] << 0x6);
-            a[3 * i + 1] = (t[2] >> 0x2) | (t[3] << 0x1) | (t[4] << 0x4) | (t[5] << 0x7);
-            a[3 * i + 2] = (t[5] >> 0x1) | (t[6] << 0x2) | (t[7] << 0x5);
+    /// The same bytes as `pack` given to `f` by chunks, the whole encoding is never stored.
+    pub fn pack_with(&self, mut f: impl FnMut(&[u8])) {
+        for chunk in self.coefficients.chunks(8) {
+            // eight coefficients take as many bytes as the width in bits
+            let mut r = [0; 14];
+            let r = &mut r[..Coefficient::<P::Modulus>::WIDTH];
+            kernel::pack(chunk, r);
+            f(r);
         }
+    }
 
-        a
+    #[cfg(any(test, feature = "low-memory"))]
+    pub(super) fn coefficients(&self) -> &[Coefficient<P::Modulus>] {
+        &self.coefficients
     }
 
-    pub fn decompress(v: &GenericArray<u8, N::CompressedLength>) -> Self {
-        let mut c = GenericArray::default();
-
-        for i in 0..(N::PolyLength::USIZE / 8) {
-            let a = &v[(3 * i)..(3 * (i + 1))];
-            let t = [
-                a[0] & 0x07,
-                (a[0] >> 0x3) & 0x07,
-                (a[0] >> 0x6) | ((a[1] << 0x2) & 0x04),
-                (a[1] >> 0x1) & 0x07,
-                (a[1] >> 0x4) & 0x07,
-                (a[1] >> 0x7) | ((a[2] << 0x1) & 0x06),
-                (a[2] >> 0x2) & 0x07,
-                (a[2] >> 0x5),
-            ];
-            for j in 0..8 {
-                c[8 * i + j] = Coefficient::decompress(t[j]);
-            }
+    /// Prepares the polynomial to be multiplied by many others.
+    pub fn precompute(&self) -> Precomputed<N, S, P> {
+        let mut coefficients = zero();
+        let mut shoup = [0; N];
+        for i in 0..N {
+            let w = Coefficient::new(self.coefficients[i].freeze() as u16);
+            shoup[i] = w.shoup();
+            coefficients[i] = w;
         }
 
-        Self::new(c)
+        Precomputed {
+            coefficients: coefficients,
+            shoup: shoup,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Checks that every coefficient is reduced, i.e. less than `q`.
+    pub fn check_reduced(&self) -> Result<(), Error> {
+        for (i, c) in self.coefficients.iter().enumerate() {
+            Coefficient::<P::Modulus>::valid_new(c.data() as u16).map_err(|e| e.shift(0, i))?;
+        }
+        Ok(())
     }
 
-    pub fn functor_2<F, S0, S1>(a: &Poly<N, S0>, b: &Poly<N, S1>, f: F) -> Self
+    /// Whether every coefficient is in `[-bound, bound]` modulo `q`,
+    /// does not branch on the coefficients.
+    pub fn is_small(&self, bound: u16) -> bool {
+        let exceed = self.coefficients.iter().fold(0, |exceed, c| {
+            exceed | ((bound as u32).wrapping_sub(c.abs() as u32) >> 31)
+        });
+        exceed == 0
+    }
+
+    pub fn functor_2<F, S0, S1>(a: &Poly<N, S0, P>, b: &Poly<N, S1, P>, f: F) -> Self
     where
-        F: Fn(CoefficientRich<S0>, CoefficientRich<S1>) -> CoefficientRich<S>,
+        F: Fn(
+            CoefficientRich<S0, P::Modulus>,
+            CoefficientRich<S1, P::Modulus>,
+        ) -> CoefficientRich<S, P::Modulus>,
         S0: PolyState,
         S1: PolyState,
     {
-        let mut r = GenericArray::default();
-
-        for i in 0..N::PolyLength::USIZE {
-            r[i] = f(
+        Self::new(array::from_fn(|i| {
+            f(
                 CoefficientRich::new(a.coefficients[i].data()),
                 CoefficientRich::new(b.coefficients[i].data()),
             )
-            .0;
-        }
-
-        Self::new(r)
+            .reduce()
+        }))
     }
 
-    pub fn functor_3<F, S0, S1, S2>(a: &Poly<N, S0>, b: &Poly<N, S1>, c: &Poly<N, S2>, f: F) -> Self
+    pub fn functor_3<F, S0, S1, S2>(
+        a: &Poly<N, S0, P>,
+        b: &Poly<N, S1, P>,
+        c: &Poly<N, S2, P>,
+        f: F,
+    ) -> Self
     where
-        F: Fn(CoefficientRich<S0>, CoefficientRich<S1>, CoefficientRich<S2>) -> CoefficientRich<S>,
+        F: Fn(
+            CoefficientRich<S0, P::Modulus>,
+            CoefficientRich<S1, P::Modulus>,
+            CoefficientRich<S2, P::Modulus>,
+        ) -> CoefficientRich<S, P::Modulus>,
         S0: PolyState,
         S1: PolyState,
         S2: PolyState,
     {
-        let mut r = GenericArray::default();
-        for i in 0..N::PolyLength::USIZE {
-            r[i] = f(
+        Self::new(array::from_fn(|i| {
+            f(
                 CoefficientRich::new(a.coefficients[i].data()),
                 CoefficientRich::new(b.coefficients[i].data()),
                 CoefficientRich::new(c.coefficients[i].data()),
             )
-            .0;
+            .reduce()
+        }))
+    }
+}
+
+impl<'b, const N: usize, O, L, R, D, P> Add<&'b Poly<N, (O, R, D), P>> for &Poly<N, (O, L, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+{
+    type Output = Poly<N, (O, L::Or<R>, D), P>;
+
+    fn add(self, other: &'b Poly<N, (O, R, D), P>) -> Self::Output {
+        let mut r = zero();
+        kernel::add(&mut r, &self.coefficients, &other.coefficients);
+        Poly::new(r)
+    }
+}
+
+impl<'b, const N: usize, O, L, R, D, P> Sub<&'b Poly<N, (O, R, D), P>> for &Poly<N, (O, L, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+{
+    type Output = Poly<N, (O, L::Or<R>, D), P>;
+
+    fn sub(self, other: &'b Poly<N, (O, R, D), P>) -> Self::Output {
+        let mut r = zero();
+        kernel::sub(&mut r, &self.coefficients, &other.coefficients);
+        Poly::new(r)
+    }
+}
+
+impl<'b, const N: usize, O, L, R, D, P> Mul<&'b Poly<N, (O, R, D), P>> for &Poly<N, (O, L, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+{
+    type Output = Poly<N, (O, L::And<R>, D), P>;
+
+    fn mul(self, other: &'b Poly<N, (O, R, D), P>) -> Self::Output {
+        let mut r = zero();
+        kernel::mul(&mut r, &self.coefficients, &other.coefficients);
+        Poly::new(r)
+    }
+}
+
+/// The polynomial which is a factor of many products, like the public parameter
+/// or the secret key. Besides the reduced coefficients it keeps the factors of Shoup,
+/// so the product needs no reduction.
+pub struct Precomputed<const N: usize, S, P = NewHope>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    coefficients: [Coefficient<P::Modulus>; N],
+    shoup: [u16; N],
+    phantom_data: PhantomData<S>,
+}
+
+impl<'b, const N: usize, O, L, R, D, P> Mul<&'b Precomputed<N, (O, R, D), P>>
+    for &Poly<N, (O, L, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    R: Size,
+    D: Domain,
+{
+    type Output = Poly<N, (O, L::And<R>, D), P>;
+
+    fn mul(self, other: &'b Precomputed<N, (O, R, D), P>) -> Self::Output {
+        let mut r = zero();
+        kernel::mul_shoup(
+            &mut r,
+            &self.coefficients,
+            &other.coefficients,
+            &other.shoup,
+        );
+        Poly::new(r)
+    }
+}
+
+impl<const N: usize, S, P> Clone for Precomputed<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn clone(&self) -> Self {
+        Precomputed {
+            coefficients: self.coefficients.clone(),
+            shoup: self.shoup,
+            phantom_data: PhantomData,
         }
-        Self::new(r)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> zeroize::Zeroize for Precomputed<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn zeroize(&mut self) {
+        self.coefficients.iter_mut().for_each(Coefficient::zeroize);
+        zeroize::Zeroize::zeroize(self.shoup.as_mut_slice());
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> Drop for Precomputed<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+impl<const N: usize, S, P> Clone for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn clone(&self) -> Self {
+        Poly::new(self.coefficients.clone())
+    }
+}
+
+impl<const N: usize, S, P> PartialEq for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.coefficients == other.coefficients
+    }
+}
+
+impl<const N: usize, S, P> Eq for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+}
+
+impl<const N: usize, S, P> fmt::Debug for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the coefficients might be secret
+        write!(f, "Poly<{}>(..)", N)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> zeroize::Zeroize for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn zeroize(&mut self) {
+        self.coefficients.iter_mut().for_each(Coefficient::zeroize);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, S, P> Drop for Poly<N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
     }
 }
 
 pub trait FromSeed {
     fn from_message(message: &GenericArray<u8, U32>) -> Self;
     fn to_message_negate(&self) -> GenericArray<u8, U32>;
-    fn random(seed: &GenericArray<u8, U32>) -> Self;
+    /// The sums `to_message_negate` decides the bits by.
+    fn margin_negate(&self) -> Margin;
+    fn random<X>(seed: &GenericArray<u8, U32>) -> Self
+    where
+        X: Xof;
+}
+
+pub trait FromSeedSmall
+where
+    Self: ReverseBits,
+{
+    fn random_small<X>(seed: &GenericArray<u8, U32>, nonce: u8) -> Self
+    where
+        X: Xof;
+    /// The same as `random_small(seed, nonce).reverse_bits()`, but stores
+    /// each coefficient in its place at once, without the permutation.
+    fn random_small_reversed<X>(
+        seed: &GenericArray<u8, U32>,
+        nonce: u8,
+    ) -> <Self as ReverseBits>::Output
+    where
+        X: Xof;
 }
 
-pub trait FromSeedSmall {
-    fn random_small(seed: &GenericArray<u8, U32>, nonce: u8) -> Self;
+// the place of the coefficient number `i`
+pub(super) fn index<const N: usize, S>(i: usize) -> usize
+where
+    S: PolyState,
+{
+    if S::BitOrder::REVERSED {
+        reverse_bits(i, N)
+    } else {
+        i
+    }
 }
 
-impl<N, S> FromSeed for Poly<N, S>
+impl<const N: usize, S, P> FromSeed for Poly<N, S, P>
 where
-    N: PolySize,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
     S: PolyState,
 {
+    // the message is in natural order, even if the coefficients are not
     fn from_message(message: &GenericArray<u8, U32>) -> Self {
-        let mut c = GenericArray::default();
+        Self::new(array::from_fn(|i| {
+            message_coefficient(message, index::<N, S>(i))
+        }))
+    }
 
-        for i in 0..N::PolyLength::USIZE {
-            let l = i % 256;
-            if (message[l / 8] & (1 << (l % 8))) != 0 {
-                c[i] = Coefficient::MIDDLE;
-            }
-        }
+    fn to_message_negate(&self) -> GenericArray<u8, U32> {
+        to_message_negate::<N, S, P>(&self.coefficients)
+    }
+
+    fn margin_negate(&self) -> Margin {
+        margin_negate::<N, S, P>(&self.coefficients)
+    }
 
+    fn random<X>(seed: &GenericArray<u8, U32>) -> Self
+    where
+        X: Xof,
+    {
+        let mut c = zero();
+        sample_uniform::<N, S, X, P>(seed, |i, t| c[i] = t);
         Self::new(c)
     }
+}
 
-    fn to_message_negate(&self) -> GenericArray<u8, U32> {
-        const BITS: usize = 256;
-        let mut t = [0; BITS];
-        let mut message = GenericArray::default();
+// the coefficient number `i` encodes the bit `i % 256` of the message
+pub(super) fn message_coefficient<M>(message: &GenericArray<u8, U32>, i: usize) -> Coefficient<M>
+where
+    M: Modulus,
+{
+    let l = i % 256;
+    let mask = (((message[l / 8] >> (l % 8)) & 1) as u16).wrapping_neg();
+    Coefficient::new((Coefficient::<M>::MIDDLE.data() as u16) & mask)
+}
 
-        for i in 0..N::PolyLength::USIZE {
-            t[i % BITS] += self.coefficients[i].flip_abs() as u32;
-        }
+// each bit is spread over `N / 256` coefficients, the bit is set
+// if the sum of their distances is less than `q / 4` per coefficient
+pub(crate) fn threshold<const N: usize, M>() -> u32
+where
+    M: Modulus,
+{
+    (M::Q as u32) * (N as u32) / 256 / 4
+}
 
-        for l in 0..BITS {
-            let quarter = Coefficient::QUARTER.data();
-            if t[l] < (quarter * (N::PolyLength::USIZE as u32) / (BITS as u32)) {
-                message[l / 8] |= 1 << (l % 8);
-            }
-        }
+/// The sums of the distances of the coefficients of each bit of the message
+/// from `q / 2`, the bit is set if its sum is less than the threshold. The sums
+/// depend on the secret key, they are for the diagnostics only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Margin {
+    pub sums: [u32; 256],
+    pub threshold: u32,
+}
 
+impl Margin {
+    /// The message the sums give, does not branch on them.
+    pub fn message(&self) -> GenericArray<u8, U32> {
+        let mut message = GenericArray::default();
+        for (l, t) in self.sums.iter().enumerate() {
+            // the sums are far less than `2 ^ 31`, so the sign bit is `t < threshold`
+            let bit = (t.wrapping_sub(self.threshold) >> 31) as u8;
+            message[l / 8] |= bit << (l % 8);
+        }
         message
     }
 
-    fn random(seed: &GenericArray<u8, U32>) -> Self {
-        use core::slice;
-        use keccak::f1600;
+    /// The least distance of the sums to the threshold, the noise of the
+    /// decryption may grow that much before a bit flips.
+    pub fn least(&self) -> u32 {
+        self.sums
+            .iter()
+            .map(|&t| (t as i64 - self.threshold as i64).unsigned_abs() as u32)
+            .min()
+            .unwrap_or(0)
+    }
 
-        let mut c = GenericArray::default();
+    // wipes the sums, does nothing without the `zeroize` feature
+    fn scrub(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+        #[cfg(not(feature = "zeroize"))]
+        let _ = self;
+    }
+}
 
-        for i in 0..(N::PolyLength::USIZE / Self::BLOCK_SIZE) {
-            let mut state = [0; 0x19];
-            {
-                let buffer =
-                    unsafe { slice::from_raw_parts_mut(state.as_mut_ptr() as *mut u8, 0xa8) };
-                buffer[0x00..0x20].clone_from_slice(seed.as_ref());
-                buffer[0x20] = i as u8;
-                buffer[0x21] = 0x1f;
-                buffer[0xa7] = 0x80;
-                f1600(&mut state);
-            }
-            let mut counter = 0;
-            'block: loop {
-                let buffer =
-                    unsafe { slice::from_raw_parts_mut(state.as_mut_ptr() as *mut u8, 0xa8) };
-                for chunk in buffer.chunks(2) {
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Margin {
+    fn zeroize(&mut self) {
+        zeroize::Zeroize::zeroize(self.sums.as_mut());
+    }
+}
+
+pub(super) fn margin_negate<const N: usize, S, P>(c: &[Coefficient<P::Modulus>]) -> Margin
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    let mut sums = [0; 256];
+    for i in 0..N {
+        sums[index::<N, S>(i) % 256] += c[i].flip_abs() as u32;
+    }
+    Margin {
+        sums,
+        threshold: threshold::<N, P::Modulus>(),
+    }
+}
+
+pub(super) fn to_message_negate<const N: usize, S, P>(
+    c: &[Coefficient<P::Modulus>],
+) -> GenericArray<u8, U32>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    let mut margin = margin_negate::<N, S, P>(c);
+    let message = margin.message();
+    margin.scrub();
+    message
+}
+
+// gives the coefficient number `i` to `f` as soon as it is sampled, so the caller
+// might consume the polynomial block by block without storing it
+pub(super) fn sample_uniform<const N: usize, S, X, P>(
+    seed: &GenericArray<u8, U32>,
+    mut f: impl FnMut(usize, Coefficient<P::Modulus>),
+) where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+    X: Xof,
+{
+    let block_size = Poly::<N, S, P>::BLOCK_SIZE;
+
+    // four independent blocks at once, the suffix is the index of the block
+    let mut state = X::new(seed);
+    for i in (0..(N / block_size)).step_by(4) {
+        let suffixes: [[u8; 1]; 4] = array::from_fn(|lane| [(i + lane) as u8]);
+        state.absorb(suffixes.each_ref().map(|s| s.as_ref()));
+
+        let mut counters = [0; 4];
+        loop {
+            for (lane, counter) in counters.iter_mut().enumerate() {
+                for offset in (0..X::RATE).step_by(2) {
+                    if *counter == block_size {
+                        break;
+                    }
+                    let mut chunk = [0; 2];
+                    state.read(lane, offset, &mut chunk);
                     let r = (chunk[0] as u16) | ((chunk[1] as u16) << 8);
-                    match Coefficient::try_new(r) {
-                        Some(t) => {
-                            c[Self::BLOCK_SIZE * i + counter] = t;
-                            counter += 1;
-                        },
-                        None => (),
-                    };
-                    if counter == Self::BLOCK_SIZE {
-                        break 'block;
+                    if let Some(t) = Coefficient::try_new(r) {
+                        f(block_size * (i + lane) + *counter, t);
+                        *counter += 1;
                     }
                 }
-                f1600(&mut state);
             }
+            if counters.iter().all(|&counter| counter == block_size) {
+                break;
+            }
+            state.squeeze();
         }
-
-        Self::new(c)
     }
 }
 
-impl<N, S> FromSeedSmall for Poly<N, S>
+impl<const N: usize, S, P> FromSeedSmall for Poly<N, S, P>
 where
-    N: PolySize,
-    S: PolyState<Size = B0>,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState<Size = Small>,
 {
-    fn random_small(seed: &GenericArray<u8, U32>, nonce: u8) -> Self {
-        use sha3::{
-            Shake256,
-            digest::{Update, ExtendableOutput, XofReader},
-        };
-
-        let mut c = GenericArray::default();
-
-        let mut ext_seed = [0; 34];
-        ext_seed[0..32].clone_from_slice(seed.as_ref());
-        ext_seed[32] = nonce;
-
-        for i in 0..(N::PolyLength::USIZE / Self::BLOCK_SIZE) {
-            ext_seed[33] = i as u8;
+    fn random_small<X>(seed: &GenericArray<u8, U32>, nonce: u8) -> Self
+    where
+        X: Xof,
+    {
+        let mut c = zero();
+        sample_small::<N, S, X, P>(seed, nonce, |i, t| c[i] = t);
+        Self::new(c)
+    }
 
-            // Compute the Hamming weight of a byte
-            let hw = |b: u8| -> i8 { (0..8).map(|i| ((b >> i) & 1) as i8).sum() };
+    fn random_small_reversed<X>(
+        seed: &GenericArray<u8, U32>,
+        nonce: u8,
+    ) -> <Self as ReverseBits>::Output
+    where
+        X: Xof,
+    {
+        let length = N;
+        let mut c = zero();
+        sample_small::<N, S, X, P>(seed, nonce, |i, t| c[reverse_bits(i, length)] = t);
+        Poly::new(c)
+    }
+}
 
-            let mut buffer = [0; Self::BLOCK_SIZE * 2];
-            Shake256::default()
-                .chain(&ext_seed[..])
-                .finalize_xof()
-                .read(buffer.as_mut());
-            for j in 0..Self::BLOCK_SIZE {
-                c[Self::BLOCK_SIZE * i + j] =
-                    Coefficient::small(hw(buffer[2 * j]) - hw(buffer[2 * j + 1]));
+// gives the coefficient number `i` to `f`, see `sample_uniform`
+pub(super) fn sample_small<const N: usize, S, X, P>(
+    seed: &GenericArray<u8, U32>,
+    nonce: u8,
+    mut f: impl FnMut(usize, Coefficient<P::Modulus>),
+) where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+    X: Xof,
+{
+    let block_size = Poly::<N, S, P>::BLOCK_SIZE;
+    let noise = Poly::<N, S, P>::NOISE;
+    let table = &Poly::<N, S, P>::NOISE_TABLE[..(noise.bound() as usize)];
+    let width = noise.bits();
+    let mask = u64::MAX >> (64 - width);
+
+    // four independent blocks at once, the suffix is the nonce and the index
+    // of the block, each coefficient takes `noise.bits()` bits of the output,
+    // the window keeps the bits read but not consumed yet
+    let mut state = X::new(seed);
+    for i in (0..(N / block_size)).step_by(4) {
+        let suffixes: [[u8; 2]; 4] = array::from_fn(|lane| [nonce, (i + lane) as u8]);
+        state.absorb(suffixes.each_ref().map(|s| s.as_ref()));
+
+        let (mut windows, mut bits, mut offset) = ([0u128; 4], 0, 0);
+        for j in 0..block_size {
+            while bits < width {
+                if offset == X::RATE {
+                    state.squeeze();
+                    offset = 0;
+                }
+                for (lane, window) in windows.iter_mut().enumerate() {
+                    let mut byte = [0];
+                    state.read(lane, offset, &mut byte);
+                    *window |= (byte[0] as u128) << bits;
+                }
+                offset += 1;
+                bits += 8;
             }
+            for (lane, window) in windows.iter_mut().enumerate() {
+                let x = noise.sample(table, (*window as u64) & mask);
+                f(block_size * (i + lane) + j, Coefficient::small(x));
+                *window >>= width;
+            }
+            bits -= width;
         }
-
-        Self::new(c)
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut windows);
     }
 }
 
@@ -332,21 +789,19 @@ pub trait ReverseBits {
     fn reverse_bits(self) -> Self::Output;
 }
 
-impl<N, S> ReverseBits for Poly<N, S>
+impl<const N: usize, S, P> ReverseBits for Poly<N, S, P>
 where
-    N: PolySize,
-    N::PolyLength: Logarithm2,
-    <N::PolyLength as Logarithm2>::Output: Unsigned,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
     S: PolyState,
 {
-    type Output = Poly<N, (<S::BitOrder as Involution>::Op, S::Size, S::Domain)>;
+    type Output = Poly<N, (<S::BitOrder as BitOrder>::Op, S::Size, S::Domain), P>;
 
     fn reverse_bits(self) -> Self::Output {
-        let mut s = Poly::new(self.coefficients);
+        let mut s = self.cast();
 
-        for i in 0..1024 {
-            let (j, _) = (0..<N::PolyLength as Logarithm2>::Output::USIZE)
-                .fold((0, i), |(y, x), _| (y * 2 + x % 2, x / 2));
+        for i in 0..N {
+            let j = reverse_bits(i, N);
             if i < j {
                 let temp = s.coefficients[i].clone();
                 s.coefficients[i] = s.coefficients[j].clone();
@@ -365,77 +820,53 @@ pub trait Ntt {
     fn inv_ntt(self) -> Self::Output;
 }
 
-fn multiply<N, S>(s: Poly<N, S>, gammas: &[u16]) -> Poly<N, S>
+// bit reversed order of the coefficients to natural order of the values, in place
+pub(super) fn ntt<const N: usize, P>(c: &mut [Coefficient<P::Modulus>])
 where
-    N: PolySize,
-    S: PolyState,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
 {
-    let mut s = s;
-
-    for i in 0..N::PolyLength::USIZE {
-        s.coefficients[i] =
-            Coefficient::montgomery_reduce((gammas[i] as u32) * s.coefficients[i].data());
-    }
-
-    s
+    kernel::multiply(c, Ring::<N, P>::GAMMAS_BITREV_MONTGOMERY);
+    kernel::transform(c, Ring::<N, P>::GAMMAS_BITREV_MONTGOMERY);
 }
 
-fn transform<BitOrder, Size, Domain>(
-    s: Poly<U1024, (BitOrder, Size, Domain)>,
-    omegas: &[u16],
-) -> Poly<U1024, (BitOrder::Op, Size, Domain::Op)>
+// natural order of the values to bit reversed order of the coefficients, in place
+pub(super) fn inv_ntt<const N: usize, P>(c: &mut [Coefficient<P::Modulus>])
 where
-    BitOrder: Involution,
-    Size: Involution,
-    Domain: Involution,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
 {
-    let mut s = Poly::new(s.coefficients);
-
-    for i in 0..10 {
-        let distance = 1 << i;
-        for start in 0..distance {
-            let mut jt_widdle = 0;
-            let mut j = start;
-            loop {
-                let w = omegas[jt_widdle] as u32;
-                jt_widdle += 1;
-                let temp = s.coefficients[j].clone();
-                s.coefficients[j] = &temp + &s.coefficients[j + distance];
-                s.coefficients[j + distance] = Coefficient::montgomery_reduce(
-                    w * (&temp - &s.coefficients[j + distance]).data(),
-                );
-                j += 2 * distance;
-                if j >= 1023 {
-                    break;
-                }
-            }
-        }
-    }
-
-    s
+    kernel::inverse_transform(c, Ring::<N, P>::OMEGAS_INV_MONTGOMERY);
+    kernel::multiply(c, Ring::<N, P>::GAMMAS_INV_BITREV_MONTGOMERY);
 }
 
-impl<BitOrder, Size, Domain> Ntt for Poly<U1024, (BitOrder, Size, Domain)>
+impl<const N: usize, O, L, D, P> Ntt for Poly<N, (O, L, D), P>
 where
-    BitOrder: Involution,
-    Size: Involution,
-    Domain: Involution,
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    D: Domain,
 {
-    type Output = Poly<U1024, (BitOrder::Op, Size, Domain::Op)>;
+    type Output = Poly<N, (O::Op, L, D::Op), P>;
 
     fn ntt(self) -> Self::Output {
-        let s = multiply(self, super::tables::GAMMAS_BITREV_MONTGOMERY.as_ref());
-        transform(s, super::tables::GAMMAS_BITREV_MONTGOMERY.as_ref())
+        let mut s = self.cast();
+        ntt::<N, P>(&mut s.coefficients);
+        s
     }
 
     fn inv_ntt(self) -> Self::Output {
-        let s = transform(self, super::tables::OMEGAS_INV_BITREV_MONTGOMERY.as_ref());
-        multiply(s, super::tables::GAMMAS_INV_MONTGOMERY.as_ref())
+        let mut s = self.cast();
+        inv_ntt::<N, P>(&mut s.coefficients);
+        s
     }
 }
 
-impl<S> Poly<U1024, S>
+impl<const N: usize, S, P> Poly<N, S, P>
 where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
     S: PolyState,
 {
     #[cfg(feature = "smallest")]
@@ -443,8 +874,8 @@ where
         use num_bigint::ToBigUint;
 
         let mut q = 0u8.to_biguint().unwrap();
-        for i in 0..1024 {
-            q *= Coefficient::Q;
+        for i in 0..N {
+            q *= P::Modulus::Q;
             q += self.coefficients[i].freeze() as u16
         }
 
@@ -457,10 +888,10 @@ where
         use num_traits::ToPrimitive;
 
         let mut q = BigUint::from_bytes_le(v);
-        let mut c = GenericArray::default();
-        for i in 0..1024 {
-            c[1023 - i] = Coefficient::new((&q % Coefficient::Q).to_u16().unwrap());
-            q /= Coefficient::Q;
+        let mut c = zero();
+        for i in 0..N {
+            c[N - 1 - i] = Coefficient::new((&q % P::Modulus::Q).to_u16().unwrap());
+            q /= P::Modulus::Q;
         }
 
         Self::new(c)
@@ -469,28 +900,258 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::{Poly, FromSeed, ReverseBits, Ntt};
-    use rac::generic_array::{
-        GenericArray,
-        sequence::GenericSequence,
-        typenum::{U1024, B0, B1},
+    use super::{
+        Poly, Ring, PolySize, FromSeed, FromSeedSmall, ReverseBits, Ntt, Coefficient, Xof, zero,
     };
+    use crate::poly::{
+        Natural, Reversed, Small, Uniform, Time, Shake128X4, Shake256X4, Modulus, ParameterSet,
+        NewHope, Toy,
+    };
+    use rac::generic_array::{GenericArray, sequence::GenericSequence, typenum::U32};
 
     #[cfg(feature = "smallest")]
     #[test]
     fn smallest() {
-        let poly = Poly::<U1024, (B0, B1, B0)>::random(&GenericArray::generate(|_| rand::random()));
+        let poly = Poly::<1024, (Natural, Uniform, Time)>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
         let dump = poly.smallest();
-        let poly_new = Poly::<U1024, (B0, B1, B0)>::from_smallest(dump.as_ref());
+        let poly_new = Poly::<1024, (Natural, Uniform, Time)>::from_smallest(dump.as_ref());
         assert_eq!(poly, poly_new);
         assert!(dump.len() <= 1739);
     }
 
     #[test]
     fn ntt() {
-        let poly = Poly::<U1024, (B0, B1, B0)>::random(&GenericArray::generate(|_| rand::random()));
-        let poly_new = poly.clone().ntt().reverse_bits().inv_ntt().reverse_bits();
+        ntt_round_trip::<1024, NewHope>()
+    }
+
+    #[test]
+    fn ntt_256() {
+        ntt_round_trip::<256, NewHope>()
+    }
+
+    #[test]
+    fn ntt_512() {
+        ntt_round_trip::<512, NewHope>()
+    }
+
+    #[test]
+    fn ntt_2048() {
+        ntt_round_trip::<2048, NewHope>()
+    }
+
+    // the smallest root of unity is derived for the other modulus,
+    // the noise is narrower
+    #[test]
+    fn ntt_other_parameters() {
+        ntt_round_trip::<256, Toy>();
+        ntt_multiplication_schoolbook::<256, Toy>();
+    }
+
+    fn ntt_round_trip<const N: usize, P>()
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        let poly = Poly::<N, (Reversed, Uniform, Time), P>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
+        let poly_new = poly.clone().ntt().inv_ntt();
 
         assert_eq!(poly.coefficients, poly_new.coefficients);
     }
+
+    #[test]
+    fn samplers() {
+        sample::<256>();
+        sample::<512>();
+        sample::<1024>();
+        sample::<2048>();
+    }
+
+    // the four-way samplers must produce the same polynomials as plain SHAKE
+    fn sample<const N: usize>()
+    where
+        Ring<N>: PolySize,
+    {
+        use sha3::{
+            Shake128, Shake256,
+            digest::{Update, ExtendableOutput, XofReader},
+        };
+
+        let seed = GenericArray::generate(|_| rand::random());
+        let nonce = rand::random();
+        let hw = |b: u8| -> i8 { (0..8).map(|i| ((b >> i) & 1) as i8).sum() };
+
+        let mut uniform = zero();
+        let mut small = zero();
+        for i in 0..(N / 64) {
+            let mut reader = Shake128::default()
+                .chain(&seed[..])
+                .chain([i as u8])
+                .finalize_xof();
+            let mut counter = 0;
+            while counter < 64 {
+                let mut chunk = [0; 2];
+                reader.read(chunk.as_mut());
+                let r = (chunk[0] as u16) | ((chunk[1] as u16) << 8);
+                if let Some(t) = Coefficient::try_new(r) {
+                    uniform[64 * i + counter] = t;
+                    counter += 1;
+                }
+            }
+
+            let mut buffer = [0; 128];
+            Shake256::default()
+                .chain(&seed[..])
+                .chain([nonce, i as u8])
+                .finalize_xof()
+                .read(buffer.as_mut());
+            for j in 0..64 {
+                small[64 * i + j] = Coefficient::small(hw(buffer[2 * j]) - hw(buffer[2 * j + 1]));
+            }
+        }
+
+        let poly = Poly::<N, (Natural, Uniform, Time)>::random::<Shake128X4>(&seed);
+        assert_eq!(poly, Poly::new(uniform));
+        let poly = Poly::<N, (Natural, Small, Time)>::random_small::<Shake256X4>(&seed, nonce);
+        assert_eq!(poly, Poly::new(small));
+        let reversed =
+            Poly::<N, (Natural, Small, Time)>::random_small_reversed::<Shake256X4>(&seed, nonce);
+        assert_eq!(reversed, poly.reverse_bits());
+    }
+
+    // any extendable output function, the output is read in short blocks,
+    // so the samplers squeeze several times per block of the polynomial
+    struct Reader {
+        seed: GenericArray<u8, U32>,
+        readers: Option<[sha3::Sha3XofReader; 4]>,
+        blocks: [[u8; 0x10]; 4],
+    }
+
+    impl Xof for Reader {
+        const RATE: usize = 0x10;
+
+        fn new(seed: &GenericArray<u8, U32>) -> Self {
+            Reader {
+                seed: *seed,
+                readers: None,
+                blocks: Default::default(),
+            }
+        }
+
+        fn absorb(&mut self, suffixes: [&[u8]; 4]) {
+            use sha3::{
+                Shake256,
+                digest::{Update, ExtendableOutput},
+            };
+
+            let seed = &self.seed;
+            self.readers = Some(
+                suffixes.map(|suffix| Shake256::default().chain(seed).chain(suffix).finalize_xof()),
+            );
+            self.squeeze();
+        }
+
+        fn read(&self, instance: usize, offset: usize, buffer: &mut [u8]) {
+            let block = &self.blocks[instance];
+            buffer.clone_from_slice(&block[offset..(offset + buffer.len())]);
+        }
+
+        fn squeeze(&mut self) {
+            use sha3::digest::XofReader;
+
+            let readers = self.readers.iter_mut().flatten();
+            for (reader, block) in readers.zip(self.blocks.iter_mut()) {
+                reader.read(block);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_short_rate() {
+        let seed = GenericArray::generate(|_| rand::random());
+        let nonce = rand::random();
+        let poly = Poly::<1024, (Natural, Small, Time)>::random_small::<Shake256X4>(&seed, nonce);
+        assert_eq!(poly, Poly::random_small::<Reader>(&seed, nonce));
+        let poly = Poly::<1024, (Natural, Uniform, Time)>::random::<Reader>(&seed);
+        assert!(poly.check_reduced().is_ok());
+    }
+
+    // the narrower noise takes a byte per coefficient, the weights of its halves
+    #[test]
+    fn sample_narrow() {
+        use sha3::{
+            Shake256,
+            digest::{Update, ExtendableOutput, XofReader},
+        };
+
+        let seed = GenericArray::generate(|_| rand::random());
+        let nonce = rand::random();
+        let hw = |b: u8| -> i8 { (0..4).map(|i| ((b >> i) & 1) as i8).sum() };
+
+        let mut small = zero();
+        for i in 0..4 {
+            let mut buffer = [0; 64];
+            Shake256::default()
+                .chain(&seed[..])
+                .chain([nonce, i as u8])
+                .finalize_xof()
+                .read(buffer.as_mut());
+            for j in 0..64 {
+                small[64 * i + j] = Coefficient::small(hw(buffer[j]) - hw(buffer[j] >> 4));
+            }
+        }
+
+        let poly =
+            Poly::<256, (Natural, Small, Time), Toy>::random_small::<Shake256X4>(&seed, nonce);
+        assert_eq!(poly, Poly::new(small));
+        assert_eq!(poly, Poly::random_small::<Reader>(&seed, nonce));
+        assert!(poly.is_small(Toy::NOISE.bound() as u16));
+    }
+
+    #[test]
+    fn ntt_multiplication() {
+        ntt_multiplication_schoolbook::<256, NewHope>();
+        ntt_multiplication_schoolbook::<1024, NewHope>();
+    }
+
+    // the product in the transformed domain is the product modulo `x ^ n + 1`,
+    // the forward transform takes the coefficients in bit reversed order
+    fn ntt_multiplication_schoolbook<const N: usize, P>()
+    where
+        Ring<N, P>: PolySize,
+        P: ParameterSet,
+    {
+        let a = Poly::<N, (Natural, Uniform, Time), P>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
+        let b = Poly::<N, (Natural, Small, Time), P>::random_small::<Shake256X4>(
+            &GenericArray::generate(|_| rand::random()),
+            0,
+        );
+
+        let q = P::Modulus::Q as i64;
+        let n = N;
+        let mut expected = zero();
+        for (i, e) in expected.iter_mut().enumerate() {
+            let mut t = 0;
+            for j in 0..n {
+                let x = (a.coefficients[j].freeze() as i64)
+                    * (b.coefficients[(n + i - j) % n].freeze() as i64);
+                t += if j <= i { x } else { -x };
+            }
+            *e = Coefficient::new(t.rem_euclid(q) as u16);
+        }
+
+        let a = a.reverse_bits().ntt();
+        let b = b.reverse_bits().ntt();
+        let r = (&a * &b).inv_ntt().reverse_bits();
+        assert_eq!(r.coefficients, expected);
+
+        // the same product with the precomputed factor
+        let r = (&a * &b.precompute()).inv_ntt().reverse_bits();
+        assert_eq!(r.coefficients, expected);
+    }
 }
@@ -0,0 +1,286 @@
+// the portable kernels, the vectorized ones must give exactly the same result
+
+use super::{
+    coefficient::Coefficient,
+    modulus::Modulus,
+    keccak::{ROUND_CONSTANTS, RHO, PI},
+    tables::reverse_bits,
+};
+use crate::Error;
+
+pub fn multiply<M>(c: &mut [Coefficient<M>], gammas: &[u16])
+where
+    M: Modulus,
+{
+    for i in 0..c.len() {
+        c[i] = Coefficient::montgomery_reduce((gammas[i] as u32) * c[i].data());
+    }
+}
+
+// the sums of the even layer are less than `2 ^ 16`, the next layer reduces them,
+// the last layer always reduces
+fn is_lazy(layer: u32, layers: u32) -> bool {
+    layer & 1 == 0 && layer + 1 != layers
+}
+
+fn butterfly<M>(c: &mut [Coefficient<M>], j: usize, k: usize, w: u16, lazy: bool)
+where
+    M: Modulus,
+{
+    let sum = c[j].data() + c[k].data();
+    let difference = c[j].lazy_sub(&c[k]);
+    c[j] = if lazy {
+        Coefficient::new(sum as u16)
+    } else {
+        Coefficient::new(Coefficient::<M>::reduce(sum))
+    };
+    c[k] = Coefficient::montgomery_reduce((w as u32) * difference);
+}
+
+// takes the coefficients in bit reversed order, gives the natural order
+pub fn transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    let layers = c.len().trailing_zeros();
+    for i in 0..layers {
+        let lazy = is_lazy(i, layers);
+        let distance = 1 << i;
+        for start in 0..distance {
+            let mut jt_widdle = 0;
+            let mut j = start;
+            loop {
+                butterfly(c, j, j + distance, omegas[jt_widdle], lazy);
+                jt_widdle += 1;
+                j += 2 * distance;
+                if j >= c.len() - 1 {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// takes the natural order, gives the coefficients in bit reversed order,
+// the twiddle factors of the layer are the powers in natural order
+pub fn inverse_transform<M>(c: &mut [Coefficient<M>], omegas: &[u16])
+where
+    M: Modulus,
+{
+    let layers = c.len().trailing_zeros();
+    let mut offset = 0;
+    for i in 0..layers {
+        let lazy = is_lazy(i, layers);
+        let distance = c.len() >> (i + 1);
+        for start in (0..c.len()).step_by(2 * distance) {
+            for j in 0..distance {
+                butterfly(c, start + j, start + j + distance, omegas[offset + j], lazy);
+            }
+        }
+        offset += distance;
+    }
+}
+
+pub fn add<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in 0..r.len() {
+        r[i] = &a[i] + &b[i];
+    }
+}
+
+pub fn sub<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in 0..r.len() {
+        r[i] = &a[i] - &b[i];
+    }
+}
+
+pub fn mul<M>(r: &mut [Coefficient<M>], a: &[Coefficient<M>], b: &[Coefficient<M>])
+where
+    M: Modulus,
+{
+    for i in 0..r.len() {
+        r[i] = &a[i] * &b[i];
+    }
+}
+
+pub fn mul_shoup<M>(
+    r: &mut [Coefficient<M>],
+    a: &[Coefficient<M>],
+    w: &[Coefficient<M>],
+    w_shoup: &[u16],
+) where
+    M: Modulus,
+{
+    for i in 0..r.len() {
+        r[i] = a[i].mul_shoup(&w[i], w_shoup[i]);
+    }
+}
+
+// the coefficients are concatenated as the little endian bit string,
+// eight of them take exactly as many bytes as the width in bits
+pub fn pack<M>(c: &[Coefficient<M>], r: &mut [u8])
+where
+    M: Modulus,
+{
+    let width = Coefficient::<M>::WIDTH;
+    for (c, r) in c.chunks(8).zip(r.chunks_mut(width)) {
+        let mut t = 0u128;
+        for (j, c) in c.iter().enumerate() {
+            t |= (c.freeze() as u128) << (width * j);
+        }
+        r.clone_from_slice(&t.to_le_bytes()[..width]);
+    }
+}
+
+pub fn unpack<M>(v: &[u8], c: &mut [Coefficient<M>]) -> Result<(), Error>
+where
+    M: Modulus,
+{
+    let width = Coefficient::<M>::WIDTH;
+    let mask = (1 << width) - 1;
+    for (i, (v, c)) in v.chunks(width).zip(c.chunks_mut(8)).enumerate() {
+        let mut buffer = [0; 16];
+        buffer[..width].clone_from_slice(v);
+        let t = u128::from_le_bytes(buffer);
+        for (j, c) in c.iter_mut().enumerate() {
+            let r = ((t >> (width * j)) & mask) as u16;
+            *c = Coefficient::valid_new(r).map_err(|e| e.shift(0, 8 * i + j))?;
+        }
+    }
+
+    Ok(())
+}
+
+// the bytes are in natural order, the coefficients might be in bit reversed order
+fn index(i: usize, length: usize, reversed: bool) -> usize {
+    if reversed {
+        reverse_bits(i, length)
+    } else {
+        i
+    }
+}
+
+// eight coefficients take as many bytes as the bits of the compressed one,
+// the bits are a little endian string
+pub fn compress<M>(c: &[Coefficient<M>], a: &mut [u8], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    let width = bits as usize;
+    let length = c.len();
+    for (i, a) in a[..(length / 8 * width)].chunks_mut(width).enumerate() {
+        let mut t = 0u64;
+        for j in 0..8 {
+            let x = c[index(8 * i + j, length, reversed)].compress(bits);
+            t |= (x as u64) << (width * j);
+        }
+        a.clone_from_slice(&t.to_le_bytes()[..width]);
+    }
+}
+
+pub fn decompress<M>(v: &[u8], c: &mut [Coefficient<M>], bits: u32, reversed: bool)
+where
+    M: Modulus,
+{
+    let width = bits as usize;
+    let length = c.len();
+    for (i, a) in v[..(length / 8 * width)].chunks(width).enumerate() {
+        let mut t = [0; 8];
+        t[..width].clone_from_slice(a);
+        let t = u64::from_le_bytes(t);
+        for j in 0..8 {
+            let x = ((t >> (width * j)) & ((1 << bits) - 1)) as u8;
+            c[index(8 * i + j, length, reversed)] = Coefficient::decompress(x, bits);
+        }
+    }
+}
+
+pub fn f1600x4(a: &mut [[u64; 4]; 25]) {
+    fn xor(a: [u64; 4], b: [u64; 4]) -> [u64; 4] {
+        [a[0] ^ b[0], a[1] ^ b[1], a[2] ^ b[2], a[3] ^ b[3]]
+    }
+
+    fn rotate(a: [u64; 4], n: u32) -> [u64; 4] {
+        [
+            a[0].rotate_left(n),
+            a[1].rotate_left(n),
+            a[2].rotate_left(n),
+            a[3].rotate_left(n),
+        ]
+    }
+
+    // `a ^ (!b & c)`
+    fn chi(a: [u64; 4], b: [u64; 4], c: [u64; 4]) -> [u64; 4] {
+        [
+            a[0] ^ (!b[0] & c[0]),
+            a[1] ^ (!b[1] & c[1]),
+            a[2] ^ (!b[2] & c[2]),
+            a[3] ^ (!b[3] & c[3]),
+        ]
+    }
+
+    for rc in &ROUND_CONSTANTS {
+        let mut c = [[0; 4]; 5];
+        for x in 0..5 {
+            c[x] = xor(xor(xor(a[x], a[x + 5]), xor(a[x + 10], a[x + 15])), a[x + 20]);
+        }
+        for x in 0..5 {
+            let d = xor(c[(x + 4) % 5], rotate(c[(x + 1) % 5], 1));
+            for y in 0..5 {
+                a[5 * y + x] = xor(a[5 * y + x], d);
+            }
+        }
+
+        let mut last = a[1];
+        for i in 0..24 {
+            let t = a[PI[i]];
+            a[PI[i]] = rotate(last, RHO[i]);
+            last = t;
+        }
+
+        for y in 0..5 {
+            let row = [a[5 * y], a[5 * y + 1], a[5 * y + 2], a[5 * y + 3], a[5 * y + 4]];
+            for x in 0..5 {
+                a[5 * y + x] = chi(row[x], row[(x + 1) % 5], row[(x + 2) % 5]);
+            }
+        }
+
+        a[0] = xor(a[0], [*rc; 4]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn f1600x4() {
+        let mut states = [[0u64; 25]; 4];
+        for state in &mut states {
+            for lane in state.iter_mut() {
+                *lane = rand::random();
+            }
+        }
+
+        let mut interleaved = [[0; 4]; 25];
+        for i in 0..25 {
+            for j in 0..4 {
+                interleaved[i][j] = states[j][i];
+            }
+        }
+
+        super::f1600x4(&mut interleaved);
+        for state in &mut states {
+            keccak::f1600(state);
+        }
+
+        for i in 0..25 {
+            for j in 0..4 {
+                assert_eq!(interleaved[i][j], states[j][i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// The state of the polynomial is a tuple of three markers, the order of the coefficients,
+// their size and the domain. The operations check the states at compile time,
+// e.g. only the polynomials in the same order and domain are added.
+
+/// The order in which the coefficients are stored.
+pub trait BitOrder {
+    type Op: BitOrder<Op = Self>;
+
+    const REVERSED: bool;
+}
+
+/// The coefficient number `i` is stored at `i`.
+pub struct Natural;
+
+/// The coefficient number `i` is stored at `reverse_bits(i)`.
+pub struct Reversed;
+
+impl BitOrder for Natural {
+    type Op = Reversed;
+
+    const REVERSED: bool = false;
+}
+
+impl BitOrder for Reversed {
+    type Op = Natural;
+
+    const REVERSED: bool = true;
+}
+
+/// The size of the coefficients, the sum is uniform if any of the terms is,
+/// the product is uniform only if both factors are.
+pub trait Size {
+    type Or<R: Size>: Size;
+    type And<R: Size>: Size;
+}
+
+/// The noise, only such polynomials are sampled by `FromSeedSmall`.
+pub struct Small;
+
+/// The coefficients are uniform modulo `q`.
+pub struct Uniform;
+
+impl Size for Small {
+    type Or<R: Size> = R;
+    type And<R: Size> = Small;
+}
+
+impl Size for Uniform {
+    type Or<R: Size> = Uniform;
+    type And<R: Size> = R;
+}
+
+/// The domain of the polynomial, `Ntt` switches it.
+pub trait Domain {
+    type Op: Domain<Op = Self>;
+}
+
+/// The coefficients of the polynomial.
+pub struct Time;
+
+/// The values of the polynomial at the roots of `x ^ N + 1`, the products are pointwise.
+pub struct Frequency;
+
+impl Domain for Time {
+    type Op = Frequency;
+}
+
+impl Domain for Frequency {
+    type Op = Time;
+}
+
+pub trait PolyState {
+    type BitOrder: BitOrder;
+    type Size: Size;
+    type Domain: Domain;
+}
+
+impl<O, L, D> PolyState for (O, L, D)
+where
+    O: BitOrder,
+    L: Size,
+    D: Domain,
+{
+    type BitOrder = O;
+    type Size = L;
+    type Domain = D;
+}
@@ -0,0 +1,317 @@
+// The low memory mode. The polynomial lives in a scratch buffer owned by the caller,
+// the view of the buffer carries the state, so the transforms work in place and
+// consume the view instead of the polynomial. The public parameter and the noise
+// are never stored, they are sampled block by block right into the arithmetic.
+
+use super::{
+    coefficient::Coefficient,
+    params::{ParameterSet, NewHope},
+    state::{PolyState, BitOrder, Size, Domain, Small},
+    poly::{self, Poly, Ring, PolySize},
+    xof::Xof,
+    portable, kernel,
+};
+use core::{array, marker::PhantomData};
+use rac::generic_array::{
+    GenericArray,
+    typenum::{Unsigned, U32},
+};
+
+/// The storage of a single polynomial.
+pub struct Scratch<const N: usize, P = NewHope>([Coefficient<P::Modulus>; N])
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet;
+
+impl<const N: usize, P> Default for Scratch<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    fn default() -> Self {
+        Scratch(array::from_fn(|_| Coefficient::default()))
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize, P> Drop for Scratch<N, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+{
+    fn drop(&mut self) {
+        self.0.iter_mut().for_each(Coefficient::zeroize);
+    }
+}
+
+/// The polynomial in the scratch buffer.
+pub struct View<'a, const N: usize, S, P = NewHope>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    coefficients: &'a mut [Coefficient<P::Modulus>; N],
+    phantom_data: PhantomData<S>,
+}
+
+impl<'a, const N: usize, S, P> View<'a, N, S, P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    S: PolyState,
+{
+    fn cast<T>(self) -> View<'a, N, T, P>
+    where
+        T: PolyState,
+    {
+        View {
+            coefficients: self.coefficients,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// The same as `FromSeedSmall::random_small`.
+    pub fn random_small<X>(
+        scratch: &'a mut Scratch<N, P>,
+        seed: &GenericArray<u8, U32>,
+        nonce: u8,
+    ) -> Self
+    where
+        S: PolyState<Size = Small>,
+        X: Xof,
+    {
+        let c = &mut scratch.0;
+        poly::sample_small::<N, S, X, P>(seed, nonce, |i, t| c[i] = t);
+        View {
+            coefficients: c,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Adds the noise, the same as `random_small` or `random_small_reversed`
+    /// depending on the order of the coefficients.
+    pub fn add_small<X>(&mut self, seed: &GenericArray<u8, U32>, nonce: u8)
+    where
+        X: Xof,
+    {
+        let c = &mut *self.coefficients;
+        poly::sample_small::<N, S, X, P>(seed, nonce, |i, t| {
+            let i = poly::index::<N, S>(i);
+            c[i] = &c[i] + &t;
+        });
+    }
+
+    /// Adds the polynomial `FromSeed::from_message`.
+    pub fn add_message(&mut self, message: &GenericArray<u8, U32>) {
+        for i in 0..self.coefficients.len() {
+            let t = poly::message_coefficient(message, poly::index::<N, S>(i));
+            self.coefficients[i] = &self.coefficients[i] + &t;
+        }
+    }
+
+    /// Subtracts the polynomial `Poly::decompress`, eight coefficients at once.
+    pub fn sub_decompressed(
+        &mut self,
+        v: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    ) {
+        let bits = P::CompressionBits::U32;
+        for (i, a) in v.chunks(bits as usize).enumerate() {
+            let mut t = <[Coefficient<P::Modulus>; 8]>::default();
+            portable::decompress(a, &mut t, bits, false);
+            for (j, t) in t.iter().enumerate() {
+                let k = poly::index::<N, S>(8 * i + j);
+                self.coefficients[k] = &self.coefficients[k] - t;
+            }
+        }
+    }
+
+    pub fn to_message_negate(&self) -> GenericArray<u8, U32> {
+        poly::to_message_negate::<N, S, P>(self.coefficients)
+    }
+
+    pub fn compress(&self) -> GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength> {
+        let mut a = GenericArray::default();
+        let bits = P::CompressionBits::U32;
+        kernel::compress(self.coefficients, &mut a, bits, S::BitOrder::REVERSED);
+        a
+    }
+
+    /// Zero if the polynomial is equal to `other`, does not branch on the coefficients.
+    pub fn diff(&self, other: &Poly<N, S, P>) -> u16 {
+        let pairs = self.coefficients.iter().zip(other.coefficients());
+        pairs.fold(0, |r, (a, b)| r | ((a.freeze() ^ b.freeze()) as u16))
+    }
+
+    /// Zero if `Poly::compress` gives `v`, compresses eight coefficients at once.
+    pub fn diff_compressed(
+        &self,
+        v: &GenericArray<u8, <Ring<N, P> as PolySize>::CompressedLength>,
+    ) -> u8 {
+        let bits = P::CompressionBits::U32;
+        let mut r = 0;
+        for (i, a) in v.chunks(bits as usize).enumerate() {
+            let mut t = <[Coefficient<P::Modulus>; 8]>::default();
+            for (j, t) in t.iter_mut().enumerate() {
+                *t = self.coefficients[poly::index::<N, S>(8 * i + j)].clone();
+            }
+            let mut b = [0; 8];
+            let b = &mut b[..a.len()];
+            portable::compress(&t, b, bits, false);
+            r |= a.iter().zip(b.iter()).fold(0, |r, (a, b)| r | (a ^ b));
+        }
+        r
+    }
+
+    pub fn to_poly(&self) -> Poly<N, S, P> {
+        Poly::new(self.coefficients.clone())
+    }
+}
+
+impl<'a, const N: usize, O, D, P> View<'a, N, (O, Small, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    D: Domain,
+{
+    /// Adds `a * s` where `a` is `FromSeed::random`, it is sampled block by block.
+    pub fn add_product_uniform<X>(
+        &mut self,
+        seed: &GenericArray<u8, U32>,
+        s: &View<'_, N, (O, Small, D), P>,
+    ) where
+        X: Xof,
+    {
+        let c = &mut *self.coefficients;
+        poly::sample_uniform::<N, (O, Small, D), X, P>(seed, |i, a| {
+            c[i] = &c[i] + &(&a * &s.coefficients[i]);
+        });
+    }
+}
+
+impl<'a, const N: usize, O, L, D, P> View<'a, N, (O, L, D), P>
+where
+    Ring<N, P>: PolySize,
+    P: ParameterSet,
+    O: BitOrder,
+    L: Size,
+    D: Domain,
+{
+    /// The product `a * b` in the scratch.
+    pub fn product<R>(
+        scratch: &'a mut Scratch<N, P>,
+        a: &Poly<N, (O, L, D), P>,
+        b: &Poly<N, (O, R, D), P>,
+    ) -> View<'a, N, (O, L::And<R>, D), P>
+    where
+        R: Size,
+    {
+        let c = &mut scratch.0;
+        kernel::mul(c, a.coefficients(), b.coefficients());
+        View {
+            coefficients: c,
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Multiplies in place by `other`.
+    pub fn mul<R>(self, other: &Poly<N, (O, R, D), P>) -> View<'a, N, (O, L::And<R>, D), P>
+    where
+        R: Size,
+    {
+        for (c, b) in self.coefficients.iter_mut().zip(other.coefficients()) {
+            *c = &*c * b;
+        }
+        self.cast()
+    }
+
+    /// The same as `Ntt::ntt`, in place.
+    pub fn ntt(self) -> View<'a, N, (O::Op, L, D::Op), P> {
+        poly::ntt::<N, P>(self.coefficients);
+        self.cast()
+    }
+
+    /// The same as `Ntt::inv_ntt`, in place.
+    pub fn inv_ntt(self) -> View<'a, N, (O::Op, L, D::Op), P> {
+        poly::inv_ntt::<N, P>(self.coefficients);
+        self.cast()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scratch, View};
+    use crate::poly::{
+        Poly, FromSeed, FromSeedSmall, Ntt, Natural, Reversed, Small, Uniform, Time, Frequency,
+        Shake128X4, Shake256X4,
+    };
+    use rac::generic_array::{GenericArray, sequence::GenericSequence};
+
+    // the same steps as the encryption, with and without the views
+    #[test]
+    fn encryption() {
+        let (seed_a, seed) = (
+            GenericArray::generate(|_| rand::random()),
+            GenericArray::generate(|_| rand::random()),
+        );
+        let message = GenericArray::generate(|_| rand::random());
+        let pk = Poly::<1024, (Natural, Small, Frequency)>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
+
+        let s = Poly::<1024, (Reversed, Small, Time)>::random_small::<Shake256X4>(&seed, 0).ntt();
+        let e = Poly::<1024, (Reversed, Small, Time)>::random_small::<Shake256X4>(&seed, 1).ntt();
+        let b = &e
+            + &(&Poly::<1024, (Natural, Uniform, Frequency)>::random::<Shake128X4>(&seed_a) * &s);
+        let e = Poly::<1024, (Natural, Small, Time)>::random_small_reversed::<Shake256X4>(&seed, 2);
+        let v = Poly::<1024, (Reversed, Small, Time)>::from_message(&message);
+        let c = &(&(&pk * &s).inv_ntt() + &e) + &v;
+        let c = c.compress();
+
+        let (mut s_scratch, mut b_scratch) = (Scratch::default(), Scratch::default());
+        let s_view = View::<1024, (Reversed, Small, Time)>::random_small::<Shake256X4>(
+            &mut s_scratch,
+            &seed,
+            0,
+        )
+        .ntt();
+        let mut b_view = View::<1024, (Reversed, Small, Time)>::random_small::<Shake256X4>(
+            &mut b_scratch,
+            &seed,
+            1,
+        )
+        .ntt();
+        b_view.add_product_uniform::<Shake128X4>(&seed_a, &s_view);
+        assert_eq!(b_view.to_poly(), b);
+        assert_eq!(b_view.diff(&b), 0);
+        assert_ne!(b_view.diff(&e.clone().ntt()), 0);
+
+        let mut c_view = s_view.mul(&pk).inv_ntt();
+        c_view.add_small::<Shake256X4>(&seed, 2);
+        c_view.add_message(&message);
+        assert_eq!(c_view.compress(), c);
+        assert_eq!(c_view.diff_compressed(&c), 0);
+        let mut c_wrong = c;
+        c_wrong[7] ^= 0x10;
+        assert_ne!(c_view.diff_compressed(&c_wrong), 0);
+    }
+
+    #[test]
+    fn decryption() {
+        let sk = Poly::<512, (Natural, Small, Frequency)>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
+        let pk = Poly::<512, (Natural, Small, Frequency)>::random::<Shake128X4>(
+            &GenericArray::generate(|_| rand::random()),
+        );
+        let c = GenericArray::generate(|_| rand::random());
+        let c_poly = Poly::<512, (Reversed, Small, Time)>::decompress(&c);
+        let expected = (&(&sk * &pk).inv_ntt() - &c_poly).to_message_negate();
+
+        let mut scratch = Scratch::default();
+        let mut view = View::product(&mut scratch, &sk, &pk).inv_ntt();
+        view.sub_decompressed(&c);
+        assert_eq!(view.to_message_negate(), expected);
+    }
+}
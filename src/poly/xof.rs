@@ -0,0 +1,182 @@
+// The samplers expand four blocks of the polynomial at once, so the extendable
+// output function runs four independent instances, each of them absorbs the seed
+// followed by its own suffix, the index of the block and maybe the nonce. The state
+// is created once per polynomial and absorbs again in place for the next blocks,
+// it is never moved, the low memory mode cannot afford a copy of it on the stack.
+
+use super::keccak::KeccakX4;
+use rac::generic_array::{GenericArray, typenum::U32};
+
+/// The extendable output function the polynomials are sampled with.
+pub trait Xof {
+    /// How many bytes of each instance are readable before the next `squeeze`,
+    /// the samplers read two bytes at once, so it must be even.
+    const RATE: usize;
+
+    /// The state of the seed, it is not readable before `absorb`.
+    fn new(seed: &GenericArray<u8, U32>) -> Self;
+
+    /// Starts over, absorbs the seed followed by the suffix of each instance,
+    /// the first block of the output is ready to read.
+    fn absorb(&mut self, suffixes: [&[u8]; 4]);
+
+    /// Reads the current block of the `instance` starting at the byte `offset`.
+    fn read(&self, instance: usize, offset: usize, buffer: &mut [u8]);
+
+    /// Moves every instance to its next block.
+    fn squeeze(&mut self);
+}
+
+/// SHAKE128, the public parameter is expanded with it.
+pub struct Shake128X4 {
+    seed: GenericArray<u8, U32>,
+    state: KeccakX4,
+}
+
+/// SHAKE256, the noise is sampled with it.
+pub struct Shake256X4 {
+    seed: GenericArray<u8, U32>,
+    state: KeccakX4,
+}
+
+macro_rules! shake {
+    ($name:ident, $rate:expr) => {
+        impl Xof for $name {
+            const RATE: usize = $rate;
+
+            fn new(seed: &GenericArray<u8, U32>) -> Self {
+                $name {
+                    seed: *seed,
+                    state: KeccakX4::default(),
+                }
+            }
+
+            fn absorb(&mut self, suffixes: [&[u8]; 4]) {
+                let (seed, state) = (&self.seed, &mut self.state);
+                state.clear();
+                for (instance, suffix) in suffixes.iter().enumerate() {
+                    let length = seed.len() + suffix.len();
+                    state.xor(instance, 0, seed.as_ref());
+                    state.xor(instance, seed.len(), suffix);
+                    // the domain separation and the padding
+                    state.xor(instance, length, &[0x1f]);
+                    state.xor(instance, Self::RATE - 1, &[0x80]);
+                }
+                state.permute();
+            }
+
+            fn read(&self, instance: usize, offset: usize, buffer: &mut [u8]) {
+                self.state.read(instance, offset, buffer)
+            }
+
+            fn squeeze(&mut self) {
+                self.state.permute()
+            }
+        }
+    };
+}
+
+shake!(Shake128X4, 0xa8);
+shake!(Shake256X4, 0x88);
+
+/// AES-256 in the counter mode, the seed is the key. The counter block of the
+/// block number `j` of an instance is its suffix padded with zeros to 12 bytes,
+/// the index of the block of the polynomial and maybe the nonce before it, followed
+/// by `j` as 4 bytes big endian, `j` starts at zero for each suffix. So the output
+/// of an instance is `AES-256(seed, suffix || 0.. || j)` for `j = 0, 1, ..`,
+/// and the instances never share a counter block, their suffixes differ.
+#[cfg(feature = "90s")]
+pub struct Aes256CtrX4 {
+    cipher: aes::Aes256,
+    nonces: [[u8; 12]; 4],
+    counter: u32,
+    blocks: [[u8; 0x40]; 4],
+}
+
+#[cfg(feature = "90s")]
+impl Xof for Aes256CtrX4 {
+    const RATE: usize = 0x40;
+
+    fn new(seed: &GenericArray<u8, U32>) -> Self {
+        use aes::cipher::NewBlockCipher;
+
+        Aes256CtrX4 {
+            cipher: aes::Aes256::new(seed),
+            nonces: [[0; 12]; 4],
+            counter: 0,
+            blocks: [[0; 0x40]; 4],
+        }
+    }
+
+    fn absorb(&mut self, suffixes: [&[u8]; 4]) {
+        for (nonce, suffix) in self.nonces.iter_mut().zip(suffixes.iter()) {
+            *nonce = [0; 12];
+            nonce[..suffix.len()].clone_from_slice(suffix);
+        }
+        self.counter = 0;
+        self.squeeze();
+    }
+
+    fn read(&self, instance: usize, offset: usize, buffer: &mut [u8]) {
+        let block = &self.blocks[instance];
+        buffer.clone_from_slice(&block[offset..(offset + buffer.len())]);
+    }
+
+    fn squeeze(&mut self) {
+        use aes::{Block, cipher::BlockEncrypt};
+
+        // four consecutive counters of each instance, all sixteen blocks at once
+        let mut blocks = [Block::default(); 16];
+        for (i, block) in blocks.iter_mut().enumerate() {
+            block[..12].clone_from_slice(&self.nonces[i / 4]);
+            block[12..].clone_from_slice(&(self.counter + (i % 4) as u32).to_be_bytes());
+        }
+        self.cipher.encrypt_blocks(&mut blocks);
+        for (i, block) in blocks.iter().enumerate() {
+            self.blocks[i / 4][(0x10 * (i % 4))..(0x10 * (i % 4 + 1))].clone_from_slice(block);
+        }
+        self.counter += 4;
+    }
+}
+
+#[cfg(all(feature = "90s", feature = "zeroize"))]
+impl Drop for Aes256CtrX4 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.blocks)
+    }
+}
+
+#[cfg(all(test, feature = "90s"))]
+mod tests {
+    use super::{Xof, Aes256CtrX4};
+    use aes::{
+        Aes256, Block,
+        cipher::{BlockEncrypt, NewBlockCipher},
+    };
+    use rac::generic_array::{GenericArray, sequence::GenericSequence};
+
+    // the counter blocks of each instance, encrypted one by one
+    #[test]
+    fn aes_counter() {
+        let seed = GenericArray::generate(|_| rand::random());
+        let suffixes: [[u8; 2]; 4] = [[7, 0], [7, 1], [7, 2], [7, 3]];
+        let mut state = Aes256CtrX4::new(&seed);
+        state.absorb(suffixes.each_ref().map(|s| s.as_ref()));
+        let cipher = Aes256::new(&seed);
+
+        for squeeze in 0..3 {
+            for (instance, suffix) in suffixes.iter().enumerate() {
+                let mut output = [0; 0x40];
+                state.read(instance, 0, &mut output);
+                for (j, chunk) in output.chunks(0x10).enumerate() {
+                    let mut block = Block::default();
+                    block[..2].clone_from_slice(suffix);
+                    block[12..].clone_from_slice(&((4 * squeeze + j) as u32).to_be_bytes());
+                    cipher.encrypt_block(&mut block);
+                    assert_eq!(chunk, &block[..]);
+                }
+            }
+            state.squeeze();
+        }
+    }
+}
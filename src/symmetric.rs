@@ -0,0 +1,137 @@
+use super::poly::{Xof, Shake128X4, Shake256X4};
+use sha3::digest::{Update, ExtendableOutput};
+
+/// The symmetric primitives the scheme is instantiated with. The polynomials
+/// are sampled with them, and the public key of the cca variant is hashed with
+/// them, so the cca `Kem` is only implemented for their hash. The keys are
+/// `Clone`, so are the markers.
+pub trait Symmetric
+where
+    Self: Clone,
+{
+    /// Expands the public seed into the parameter.
+    type Uniform: Xof;
+    /// Expands the secret seed and the nonce into the noise.
+    type Noise: Xof;
+    /// Hashes the public key, the hash of the cca `Kem`.
+    type Hash: Default + Update + ExtendableOutput;
+}
+
+/// SHAKE128 and SHAKE256, as the specification says.
+#[derive(Clone)]
+pub struct Keccak;
+
+impl Symmetric for Keccak {
+    type Uniform = Shake128X4;
+    type Noise = Shake256X4;
+    type Hash = sha3::Shake256;
+}
+
+/// The 90s variant for the hardware with AES-NI but without fast Keccak,
+/// AES-256-CTR samples the polynomials, see `Aes256CtrX4`, and SHA-256 hashes
+/// the public key, see `Sha2`. The `Kem` hash is `Sha2` then.
+#[cfg(feature = "90s")]
+#[derive(Clone)]
+pub struct Aes;
+
+#[cfg(feature = "90s")]
+impl Symmetric for Aes {
+    type Uniform = super::poly::Aes256CtrX4;
+    type Noise = super::poly::Aes256CtrX4;
+    type Hash = Sha2;
+}
+
+#[cfg(feature = "90s")]
+pub use self::mgf::Sha2;
+
+#[cfg(feature = "90s")]
+mod mgf {
+    use sha2::{Sha256, Digest};
+    use sha3::digest::{Update, ExtendableOutput, XofReader};
+
+    /// MGF1 of PKCS #1 v2.2 (RFC 8017, B.2.1) with SHA-256, the output is
+    /// `SHA-256(input || C(0)) || SHA-256(input || C(1)) || ..`, where `C(i)` is
+    /// the block number `i` as 4 bytes big endian. So the 32 byte hashes of the
+    /// scheme, the hash of the public key, of the cipher text and the shared secret,
+    /// are a single SHA-256, and the longer outputs are its consecutive blocks.
+    #[derive(Clone, Default)]
+    pub struct Sha2(Sha256);
+
+    pub struct Sha2Reader {
+        hasher: Sha256,
+        counter: u32,
+        block: [u8; 32],
+        position: usize,
+    }
+
+    impl Update for Sha2 {
+        fn update(&mut self, data: impl AsRef<[u8]>) {
+            Update::update(&mut self.0, data)
+        }
+    }
+
+    impl ExtendableOutput for Sha2 {
+        type Reader = Sha2Reader;
+
+        fn finalize_xof(self) -> Self::Reader {
+            Sha2Reader {
+                hasher: self.0,
+                counter: 0,
+                block: [0; 32],
+                position: 32,
+            }
+        }
+
+        fn finalize_xof_reset(&mut self) -> Self::Reader {
+            core::mem::take(self).finalize_xof()
+        }
+    }
+
+    impl XofReader for Sha2Reader {
+        fn read(&mut self, buffer: &mut [u8]) {
+            for b in buffer {
+                if self.position == self.block.len() {
+                    let mut hasher = self.hasher.clone();
+                    Update::update(&mut hasher, self.counter.to_be_bytes());
+                    self.block.clone_from_slice(&hasher.finalize());
+                    self.counter += 1;
+                    self.position = 0;
+                }
+                *b = self.block[self.position];
+                self.position += 1;
+            }
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    impl Drop for Sha2Reader {
+        fn drop(&mut self) {
+            zeroize::Zeroize::zeroize(&mut self.block)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "90s"))]
+mod tests {
+    use super::Sha2;
+    use sha2::{Sha256, Digest};
+    use sha3::digest::{Update, ExtendableOutput, XofReader};
+
+    #[test]
+    fn mgf() {
+        let mut output = [0; 80];
+        Sha2::default()
+            .chain(b"newhope")
+            .finalize_xof()
+            .read(&mut output);
+
+        let blocks = [
+            Sha256::digest(b"newhope\0\0\0\0"),
+            Sha256::digest(b"newhope\0\0\0\x01"),
+            Sha256::digest(b"newhope\0\0\0\x02"),
+        ];
+        assert_eq!(&output[..32], &blocks[0][..]);
+        assert_eq!(&output[32..64], &blocks[1][..]);
+        assert_eq!(&output[64..], &blocks[2][..16]);
+    }
+}
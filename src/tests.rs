@@ -1,39 +1,518 @@
-use crate::{Cpa, Cca, Pke, Parameter, h};
+use crate::{Cpa, Cca, Pke, Parameter, KemExt, Legacy, Error, Decode, h};
 use pq_kem::Kem;
-use sha3::Shake256;
-use rac::generic_array::{GenericArray, sequence::GenericSequence, typenum::U1024};
+use sha3::{
+    Shake256,
+    digest::{Update, ExtendableOutput},
+};
+use rac::{
+    LineValid,
+    generic_array::{
+        GenericArray,
+        sequence::GenericSequence,
+        typenum::{Unsigned, U32, U512, U1024},
+    },
+};
 use wasm_bindgen_test::*;
 
+mod drbg;
+mod kat;
+mod timing;
+#[cfg(feature = "low-memory")]
+mod stack;
+
 #[wasm_bindgen_test]
 #[test]
 fn pke() {
-    let pke = Parameter::<U1024>::new(&GenericArray::generate(|_| rand::random()));
-    let (pk_a, sk_a) = pke.generate(&GenericArray::generate(|_| rand::random()));
-    let plain_a = GenericArray::generate(|_| rand::random());
-    let (pk_b, ct) = pke.encrypt(&GenericArray::generate(|_| rand::random()), &pk_a, &plain_a);
-    let plain_b = Parameter::<U1024>::decrypt(&pk_b, &sk_a, &ct);
-    assert_eq!(plain_a, plain_b);
+    encryption::<Parameter<1024>>()
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn pke_512() {
+    encryption::<Parameter<512>>()
 }
 
 #[wasm_bindgen_test]
 #[test]
 fn cpa() {
-    kem::<Cpa<U1024>>()
+    kem::<Cpa<1024>>()
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn cpa_512() {
+    kem::<Cpa<512>>()
 }
 
 #[wasm_bindgen_test]
 #[test]
 fn cca() {
-    kem::<Cca<U1024>>()
+    kem::<Cca<1024>>()
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn cca_512() {
+    kem::<Cca<512>>()
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn kem_ext() {
+    kem_facade::<Cpa<512>>();
+    kem_facade::<Cca<512>>();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn pke_codec() {
+    encryption_codec::<Parameter<1024>>();
+    encryption_codec::<Parameter<512>>();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn cpa_codec() {
+    kem_codec::<Cpa<1024>>();
+    kem_codec::<Cpa<512>>();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn cca_codec() {
+    kem_codec::<Cca<1024>>();
+    kem_codec::<Cca<512>>();
+}
+
+#[cfg(feature = "90s")]
+#[wasm_bindgen_test]
+#[test]
+fn aes() {
+    use crate::{Aes, Sha2};
+
+    encryption::<Parameter<1024, Aes>>();
+    encryption::<Parameter<512, Aes>>();
+    kem_with::<Cpa<1024, Aes>, Sha2>();
+    kem_with::<Cca<1024, Aes>, Sha2>();
+    kem_with::<Cca<512, Aes>, Sha2>();
+
+    // the public key is hashed with SHA-256 as well
+    let (pk, _) = <Cca<512, Aes> as Kem<Sha2>>::generate_pair(&GenericArray::default());
+    assert_eq!(pk.hash(), &h::<Sha2, _, GenericArray<u8, U32>>(&pk));
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn other_parameters() {
+    use crate::{Keccak, poly::Toy};
+    use crate::cca::PublicKeyCca;
+
+    encryption::<Parameter<256, Keccak, Toy>>();
+    encryption_codec::<Parameter<256, Keccak, Toy>>();
+    kem::<Cpa<256, Keccak, Toy>>();
+    kem_codec::<Cpa<256, Keccak, Toy>>();
+    kem_codec::<Cca<256, Keccak, Toy>>();
+
+    // the coefficient takes 13 bits, the compressed one takes 4 bits
+    type K = Cca<256, Keccak, Toy>;
+    let (pk, sk) = <K as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    let (ct, _) = <K as KemExt<Shake256>>::encapsulate_key(&GenericArray::default(), &pk);
+    assert_eq!(pk.clone_line().len(), 256 / 8 * 13 + 32);
+    assert_eq!(ct.clone_line().len(), 256 / 8 * 13 + 256 / 8 * 4 + 32);
+    pk.validate().unwrap();
+    sk.validate_pair(&pk).unwrap();
+
+    let mut pk_bytes = pk.clone_line();
+    pk_bytes[0] = 0xff;
+    pk_bytes[1] |= 0x1f;
+    assert_eq!(
+        PublicKeyCca::<256, Keccak, Toy>::decode(&pk_bytes).err(),
+        Some(Error::InvalidCoefficient {
+            offset: 0,
+            index: 0,
+            value: 0x1fff,
+        }),
+    );
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn gaussian() {
+    use crate::{Keccak, poly::Gaussian};
+
+    encryption::<Parameter<512, Keccak, Gaussian>>();
+    encryption_codec::<Parameter<512, Keccak, Gaussian>>();
+    kem::<Cpa<512, Keccak, Gaussian>>();
+    kem::<Cca<512, Keccak, Gaussian>>();
+    kem_codec::<Cca<512, Keccak, Gaussian>>();
+
+    // the noise is wider than the binomial, still less than the cut
+    type K = Cca<512, Keccak, Gaussian>;
+    let (pk, sk) = <K as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    pk.validate().unwrap();
+    sk.validate_pair(&pk).unwrap();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn margin() {
+    type P = Parameter<1024>;
+
+    let pke = P::new(&GenericArray::generate(|_| rand::random()));
+    let (pk_a, sk_a) = pke.generate(&GenericArray::generate(|_| rand::random()));
+    let plain = GenericArray::generate(|_| rand::random());
+    let (pk_b, ct) = pke.encrypt(&GenericArray::generate(|_| rand::random()), &pk_a, &plain);
+    let (message, margin) = P::decrypt_with_margin(&pk_b, &sk_a, &ct);
+    assert_eq!(message, plain);
+    assert_eq!(message, P::decrypt(&pk_b, &sk_a, &ct));
+
+    // four coefficients per bit, `q / 4` each
+    assert_eq!(margin.threshold, 12289);
+    for (l, &t) in margin.sums.iter().enumerate() {
+        assert_eq!(t < margin.threshold, (plain[l / 8] >> (l % 8)) & 1 == 1);
+    }
+    // the bits are far from flipping
+    assert!(margin.least() > margin.threshold / 4);
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn legacy() {
+    legacy_sizes::<U512>();
+    legacy_sizes::<U1024>();
+}
+
+fn encryption<P>()
+where
+    P: Pke,
+{
+    let pke = P::new(&GenericArray::generate(|_| rand::random()));
+    let (pk_a, sk_a) = pke.generate(&GenericArray::generate(|_| rand::random()));
+    let plain_a = GenericArray::generate(|_| rand::random());
+    let (pk_b, ct) = pke.encrypt(&GenericArray::generate(|_| rand::random()), &pk_a, &plain_a);
+    let plain_b = P::decrypt(&pk_b, &sk_a, &ct);
+    assert_eq!(plain_a, plain_b);
+}
+
+// the code written against the `typenum` sizes
+fn legacy_sizes<N>()
+where
+    N: Legacy,
+    N::Cpa: Kem<Shake256>,
+    N::Cca: Kem<Shake256>,
+{
+    type PublicKey<K> = <K as Kem<Shake256>>::PublicKey;
+
+    let packed = N::USIZE / 8 * 14;
+    assert_eq!(<PublicKey<N::Cpa> as LineValid>::Length::USIZE, packed + 32);
+    assert_eq!(<PublicKey<N::Cca> as LineValid>::Length::USIZE, packed + 32);
+    kem::<N::Cpa>();
+    kem::<N::Cca>();
 }
 
 fn kem<K>()
 where
     K: Kem<Shake256>,
+{
+    kem_with::<K, Shake256>()
+}
+
+fn kem_with<K, D>()
+where
+    K: Kem<D>,
+    D: Default + Update + ExtendableOutput,
 {
     let (pk, sk) = K::generate_pair(&GenericArray::generate(|_| rand::random()));
-    let pk_hash = h::<Shake256, _, _>(&pk);
+    let pk_hash = h::<D, _, _>(&pk);
     let (ct, key_b) = K::encapsulate(&GenericArray::generate(|_| rand::random()), &pk, &pk_hash);
     let key_a = K::decapsulate(&sk, &pk_hash, &ct);
     assert_eq!(key_a, key_b);
 }
+
+fn kem_facade<K>()
+where
+    K: KemExt<Shake256>,
+{
+    let (pk, sk) = K::generate_pair(&GenericArray::generate(|_| rand::random()));
+    let (ct, key_b) = K::encapsulate_key(&GenericArray::generate(|_| rand::random()), &pk);
+    let key_a = K::decapsulate_key(&sk, &ct);
+    assert_eq!(key_a, key_b);
+}
+
+fn encryption_codec<P>()
+where
+    P: Pke,
+{
+    let pke = P::new(&GenericArray::generate(|_| rand::random()));
+    let (pk_a, sk_a) = pke.generate(&GenericArray::generate(|_| rand::random()));
+    let pk_a_bytes = pk_a.clone_line();
+    let sk_a_bytes = sk_a.clone_line();
+    let pk_a = P::PublicKey::try_clone_array(&pk_a_bytes).unwrap();
+    let sk_a = P::SecretKey::try_clone_array(&sk_a_bytes).unwrap();
+    assert_eq!(pk_a.clone_line(), pk_a_bytes);
+    assert_eq!(sk_a.clone_line(), sk_a_bytes);
+
+    let plain_a = GenericArray::generate(|_| rand::random());
+    let (pk_b, ct) = pke.encrypt(&GenericArray::generate(|_| rand::random()), &pk_a, &plain_a);
+    let pk_b_bytes = pk_b.clone_line();
+    let pk_b = P::PublicKey::try_clone_array(&pk_b_bytes).unwrap();
+    assert_eq!(pk_b.clone_line(), pk_b_bytes);
+
+    // the cipher goes over the wire after the public key of the sender
+    let cipher_bytes = [pk_b_bytes.as_ref(), ct.as_ref()].concat();
+    let (pk_b_bytes, ct_bytes) = cipher_bytes.split_at(pk_b_bytes.len());
+    let pk_b_decoded =
+        P::PublicKey::try_clone_array(&pk_b_bytes.iter().copied().collect()).unwrap();
+    let ct_decoded = ct_bytes.iter().copied().collect();
+    assert_eq!(pk_b_decoded.clone_line(), pk_b.clone_line());
+    assert_eq!(ct_decoded, ct);
+
+    let plain_b = P::decrypt(&pk_b_decoded, &sk_a, &ct_decoded);
+    assert_eq!(plain_a, plain_b);
+    assert_eq!(P::decrypt(&pk_b, &sk_a, &ct), plain_b);
+}
+
+fn kem_codec<K>()
+where
+    K: Kem<Shake256>,
+{
+    let (pk, sk) = K::generate_pair(&GenericArray::generate(|_| rand::random()));
+    let pk_bytes = pk.clone_line();
+    let sk_bytes = sk.clone_line();
+    let pk_decoded = K::PublicKey::try_clone_array(&pk_bytes).unwrap();
+    let sk_decoded = K::SecretKey::try_clone_array(&sk_bytes).unwrap();
+    assert_eq!(pk_decoded.clone_line(), pk_bytes);
+    assert_eq!(sk_decoded.clone_line(), sk_bytes);
+
+    // the decoded public key must encapsulate exactly like the original one
+    let seed = GenericArray::generate(|_| rand::random());
+    let pk_hash = h::<Shake256, _, _>(&pk);
+    let (ct, key_b) = K::encapsulate(&seed, &pk, &pk_hash);
+    let (ct_decoded, key_b_decoded) = K::encapsulate(&seed, &pk_decoded, &pk_hash);
+    assert_eq!(ct.clone_line(), ct_decoded.clone_line());
+    assert_eq!(key_b, key_b_decoded);
+
+    let ct_bytes = ct.clone_line();
+    let ct = K::CipherText::try_clone_array(&ct_bytes).unwrap();
+    assert_eq!(ct.clone_line(), ct_bytes);
+
+    let key_a = K::decapsulate(&sk_decoded, &pk_hash, &ct);
+    assert_eq!(key_a, key_b);
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn cca_hash() {
+    let (pk, sk) = <Cca<1024> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    let pk_hash: GenericArray<u8, U32> = h::<Shake256, _, _>(&pk);
+    assert_eq!(pk.hash(), &pk_hash);
+    assert_eq!(sk.public_key().hash(), &pk_hash);
+
+    // the keys carry the hash, the one passed by the caller does not matter
+    let seed = GenericArray::generate(|_| rand::random());
+    let (ct, key_b) = <Cca<1024> as KemExt<Shake256>>::encapsulate_key(&seed, &pk);
+    let (ct_wrong, key_b_wrong) =
+        <Cca<1024> as Kem<Shake256>>::encapsulate(&seed, &pk, &GenericArray::default());
+    assert_eq!(ct.clone_line(), ct_wrong.clone_line());
+    assert_eq!(key_b, key_b_wrong);
+    let key_a = <Cca<1024> as Kem<Shake256>>::decapsulate(&sk, &GenericArray::default(), &ct);
+    assert_eq!(key_a, key_b);
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn lazy_parameter() {
+    use crate::cca::PublicKeyCca;
+
+    // the low memory mode never expands it
+    let expands = cfg!(not(feature = "low-memory"));
+
+    let (pk, sk) = <Cca<512> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    assert_eq!(pk.is_expanded(), expands);
+
+    let pk_decoded = PublicKeyCca::<512>::decode(&pk.clone_line()).unwrap();
+    assert!(!pk_decoded.is_expanded());
+    assert_eq!(pk_decoded.hash(), pk.hash());
+    pk_decoded.validate().unwrap();
+    assert!(!pk_decoded.is_expanded());
+
+    // the encapsulation expands it for itself, `expand` keeps it and the clone as well
+    let seed = GenericArray::generate(|_| rand::random());
+    let (ct, key_b) = <Cca<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk_decoded);
+    assert!(!pk_decoded.is_expanded());
+    let mut pk_expanded = pk_decoded.clone();
+    pk_expanded.expand();
+    assert!(pk_expanded.is_expanded());
+    assert!(pk_expanded.clone().is_expanded());
+    pk_expanded.validate().unwrap();
+    let (ct_expanded, key_b_expanded) =
+        <Cca<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk_expanded);
+    assert_eq!(ct_expanded.clone_line(), ct.clone_line());
+    assert_eq!(key_b_expanded, key_b);
+    assert_eq!(
+        <Cca<512> as KemExt<Shake256>>::decapsulate_key(&sk, &ct),
+        key_b
+    );
+
+    // the keys are shared between the threads
+    fn sync<T: Sync>() {}
+    sync::<PublicKeyCca<512>>();
+    sync::<crate::cca::SecretKeyCca<512>>();
+    sync::<crate::cpa::PublicKeyCpa<512>>();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn precomputed() {
+    // the precomputed keys give exactly the same cipher texts and shared secrets
+    let seed = GenericArray::generate(|_| rand::random());
+    let (pk, _) = <Cca<512> as Kem<Shake256>>::generate_pair(&seed);
+    let (mut pk_pre, mut sk_pre) = <Cca<512> as Kem<Shake256>>::generate_pair(&seed);
+    assert!(!pk_pre.is_precomputed() && !sk_pre.is_precomputed());
+    pk_pre.precompute();
+    sk_pre.precompute();
+    assert!(pk_pre.is_precomputed() && sk_pre.is_precomputed());
+
+    let seed = GenericArray::generate(|_| rand::random());
+    let (ct, key_b) = <Cca<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk);
+    let (ct_pre, key_b_pre) = <Cca<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk_pre);
+    assert_eq!(ct.clone_line(), ct_pre.clone_line());
+    assert_eq!(key_b, key_b_pre);
+    assert_eq!(
+        <Cca<512> as KemExt<Shake256>>::decapsulate_key(&sk_pre, &ct),
+        key_b
+    );
+
+    let seed = GenericArray::generate(|_| rand::random());
+    let (pk, _) = <Cpa<512> as Kem<Shake256>>::generate_pair(&seed);
+    let (mut pk_pre, mut sk_pre) = <Cpa<512> as Kem<Shake256>>::generate_pair(&seed);
+    pk_pre.precompute();
+    sk_pre.precompute();
+    let seed = GenericArray::generate(|_| rand::random());
+    let (ct, key_b) = <Cpa<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk);
+    let (ct_pre, key_b_pre) = <Cpa<512> as KemExt<Shake256>>::encapsulate_key(&seed, &pk_pre);
+    assert_eq!(ct.clone_line(), ct_pre.clone_line());
+    assert_eq!(key_b, key_b_pre);
+    assert_eq!(
+        <Cpa<512> as KemExt<Shake256>>::decapsulate_key(&sk_pre, &ct),
+        key_b
+    );
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn validate() {
+    use crate::cpa::{PublicKeyCpa, SecretKeyCpa};
+
+    let (pk, sk) = <Cpa<1024> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    let seed = GenericArray::generate(|i| i as u8);
+    let (pk_other, _) = <Cpa<1024> as Kem<Shake256>>::generate_pair(&seed);
+    pk.validate().unwrap();
+    sk.validate_pair(&pk).unwrap();
+    assert_eq!(sk.validate_pair(&pk_other), Err(Error::KeyMismatch));
+
+    let mut pk_bytes = pk.clone_line();
+    pk_bytes[0] ^= 0x80;
+    let pk_tampered = PublicKeyCpa::<1024>::try_clone_array(&pk_bytes).unwrap();
+    pk_tampered.validate().unwrap();
+    assert_eq!(sk.validate_pair(&pk_tampered), Err(Error::KeyMismatch));
+
+    let mut sk_bytes = sk.clone_line();
+    sk_bytes[0] ^= 0x80;
+    let sk_tampered = SecretKeyCpa::<1024>::try_clone_array(&sk_bytes).unwrap();
+    assert_eq!(sk_tampered.validate(), Err(Error::InvalidSecret));
+
+    let (pk, sk) = <Cca<512> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    pk.validate().unwrap();
+    sk.validate().unwrap();
+    sk.validate_pair(&pk).unwrap();
+}
+
+#[wasm_bindgen_test]
+#[test]
+fn decode_error() {
+    use crate::{cpa::PublicKeyCpa, cca::SecretKeyCca};
+
+    let (pk, sk) = <Cca<512> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    let sk_bytes = sk.clone_line();
+    let pk_bytes = pk.clone_line();
+    // packed secret polynomial, 7 bytes per 4 coefficients
+    let packed = 7 * 512 / 4;
+
+    assert_eq!(
+        PublicKeyCpa::<512>::decode(&pk_bytes[1..]).err(),
+        Some(Error::WrongLength {
+            expected: pk_bytes.len(),
+            actual: pk_bytes.len() - 1,
+        }),
+    );
+
+    // the second coefficient of the embedded public key is `0x3fff`
+    let mut bytes = sk_bytes;
+    bytes[packed + 1] |= 0xc0;
+    bytes[packed + 2] = 0xff;
+    bytes[packed + 3] |= 0x0f;
+    assert_eq!(
+        SecretKeyCca::<512>::decode(&bytes).err(),
+        Some(Error::InvalidCoefficient {
+            offset: packed,
+            index: 1,
+            value: 0x3fff,
+        }),
+    );
+    assert!(SecretKeyCca::<512>::try_clone_array(&bytes).is_err());
+
+    // the embedded public key is not generated from the secret, but matches
+    // the embedded hash, the decoding does not expand the parameter to notice it
+    let mut bytes = sk_bytes;
+    bytes[packed] ^= 0x01;
+    let pk_tampered = PublicKeyCpa::<512>::decode(&bytes[packed..][..pk_bytes.len()]).unwrap();
+    let pk_hash: GenericArray<u8, U32> = h::<Shake256, _, _>(&pk_tampered);
+    let position = bytes.len() - 64;
+    bytes[position..(position + 32)].clone_from_slice(&pk_hash);
+    let sk_tampered = SecretKeyCca::<512>::decode(&bytes).unwrap();
+    assert!(!sk_tampered.public_key().is_expanded());
+    assert_eq!(sk_tampered.validate(), Err(Error::InconsistentPublicKey));
+
+    // the hash of the embedded public key
+    let mut bytes = sk_bytes;
+    let position = bytes.len() - 64;
+    bytes[position] ^= 0x01;
+    assert_eq!(
+        SecretKeyCca::<512>::decode(&bytes).err(),
+        Some(Error::InconsistentPublicKey),
+    );
+}
+
+#[test]
+fn error_display() {
+    use std::string::ToString;
+
+    let e = Error::InvalidCoefficient {
+        offset: 896,
+        index: 1,
+        value: 0x3fff,
+    };
+    assert_eq!(
+        e.to_string(),
+        "coefficient 1 of the polynomial at byte 896 is 16383, not less than q",
+    );
+}
+
+#[test]
+fn secret_debug() {
+    use std::format;
+
+    let (_, sk) = <Cca<512> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    assert_eq!(format!("{:?}", sk), "SecretKeyCca(..)");
+}
+
+#[cfg(feature = "zeroize")]
+#[test]
+fn zeroize() {
+    use zeroize::Zeroize;
+
+    let (_, mut sk) = <Cpa<512> as Kem<Shake256>>::generate_pair(&GenericArray::default());
+    sk.zeroize();
+    assert!(sk.clone_line().iter().all(|b| *b == 0));
+}
@@ -0,0 +1,101 @@
+use aes::{
+    Aes256,
+    cipher::{BlockEncrypt, NewBlockCipher},
+};
+use super::kat::hex;
+use rac::generic_array::{GenericArray, ArrayLength};
+
+/// The deterministic `randombytes` of the NIST PQC reference code,
+/// AES-256 CTR DRBG without derivation function and personalization.
+pub struct Drbg {
+    key: [u8; 32],
+    v: [u8; 16],
+}
+
+impl Drbg {
+    pub fn new(entropy: &[u8; 48]) -> Self {
+        let mut drbg = Drbg {
+            key: [0; 32],
+            v: [0; 16],
+        };
+        drbg.update(Some(entropy));
+        drbg
+    }
+
+    fn increment(&mut self) {
+        for b in self.v.iter_mut().rev() {
+            let (r, overflow) = b.overflowing_add(1);
+            *b = r;
+            if !overflow {
+                break;
+            }
+        }
+    }
+
+    fn block(&mut self, cipher: &Aes256) -> [u8; 16] {
+        self.increment();
+        let mut block = GenericArray::from(self.v);
+        cipher.encrypt_block(&mut block);
+        block.into()
+    }
+
+    fn update(&mut self, provided: Option<&[u8; 48]>) {
+        let cipher = Aes256::new(&GenericArray::from(self.key));
+        let mut temp = [0; 48];
+        for chunk in temp.chunks_mut(16) {
+            chunk.clone_from_slice(&self.block(&cipher));
+        }
+        if let Some(provided) = provided {
+            for (t, p) in temp.iter_mut().zip(provided.iter()) {
+                *t ^= p;
+            }
+        }
+        self.key.clone_from_slice(&temp[..32]);
+        self.v.clone_from_slice(&temp[32..]);
+    }
+
+    /// The `randombytes` call.
+    pub fn fill(&mut self, x: &mut [u8]) {
+        let cipher = Aes256::new(&GenericArray::from(self.key));
+        for chunk in x.chunks_mut(16) {
+            let block = self.block(&cipher);
+            chunk.clone_from_slice(&block[..chunk.len()]);
+        }
+        self.update(None);
+    }
+
+    /// The reference code draws every 32 bytes secret by a separate
+    /// `randombytes` call, do the same to reproduce the seed.
+    pub fn seed<L>(&mut self) -> GenericArray<u8, L>
+    where
+        L: ArrayLength<u8>,
+    {
+        let mut seed = GenericArray::default();
+        for chunk in seed.chunks_mut(32) {
+            self.fill(chunk);
+        }
+        seed
+    }
+}
+
+// the first two seeds of every `PQCkemKAT_*.req`
+#[test]
+fn request_seeds() {
+    let mut entropy = [0; 48];
+    for (i, e) in entropy.iter_mut().enumerate() {
+        *e = i as u8;
+    }
+    let mut drbg = Drbg::new(&entropy);
+
+    let mut seed = [0; 48];
+    drbg.fill(&mut seed);
+    assert_eq!(
+        hex("061550234D158C5EC95595FE04EF7A25767F2E24CC2BC479D09D86DC9ABCFDE7056A8C266F9EF97ED08541DBD2E1FFA1"),
+        &seed[..],
+    );
+    drbg.fill(&mut seed);
+    assert_eq!(
+        hex("D81C4D8D734FCBFBEADE3D3F8A039FAA2A2C9957E835AD55B22E75BF57BB556AC81ADDE6AEEB4A5A875C3BFCADFA958F"),
+        &seed[..],
+    );
+}
@@ -0,0 +1,168 @@
+use super::drbg::Drbg;
+use crate::{Cpa, Cca, h};
+use pq_kem::Kem;
+use sha3::{
+    Shake256,
+    digest::{Update, ExtendableOutput, XofReader},
+};
+use rac::LineValid;
+use std::{fs, path::Path, string::String, vec::Vec};
+
+pub fn hex(s: &str) -> Vec<u8> {
+    (0..(s.len() / 2))
+        .map(|i| u8::from_str_radix(&s[(2 * i)..(2 * i + 2)], 16).unwrap())
+        .collect()
+}
+
+#[derive(Default)]
+struct Record {
+    count: String,
+    seed: Vec<u8>,
+    pk: Vec<u8>,
+    sk: Vec<u8>,
+    ct: Vec<u8>,
+    ss: Vec<u8>,
+}
+
+fn records(name: &str) -> Vec<Record> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("kat").join(name);
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+
+    let mut records = Vec::new();
+    let mut record = Record::default();
+    for line in text.lines().map(str::trim) {
+        let mut parts = line.splitn(2, " = ");
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        match key {
+            "count" => record.count = value.into(),
+            "seed" => record.seed = hex(value),
+            "pk" => record.pk = hex(value),
+            "sk" => record.sk = hex(value),
+            "ct" => record.ct = hex(value),
+            "ss" => {
+                record.ss = hex(value);
+                records.push(record);
+                record = Record::default();
+            },
+            _ => (),
+        }
+    }
+
+    records
+}
+
+fn run<K>(name: &str)
+where
+    K: Kem<Shake256>,
+{
+    let records = records(name);
+    assert!(!records.is_empty(), "{} has no records", name);
+
+    for record in records {
+        let count = &record.count;
+        let mut entropy = [0; 48];
+        entropy.clone_from_slice(&record.seed);
+        let mut drbg = Drbg::new(&entropy);
+
+        let (pk, sk) = K::generate_pair(&drbg.seed());
+        assert_eq!(&pk.clone_line()[..], &record.pk[..], "pk, count = {}", count);
+        assert_eq!(&sk.clone_line()[..], &record.sk[..], "sk, count = {}", count);
+
+        let pk_hash = h::<Shake256, _, _>(&pk);
+        let (ct, ss) = K::encapsulate(&drbg.seed(), &pk, &pk_hash);
+        assert_eq!(&ct.clone_line()[..], &record.ct[..], "ct, count = {}", count);
+        assert_eq!(&ss[..], &record.ss[..], "ss, count = {}", count);
+
+        let ss = K::decapsulate(&sk, &pk_hash, &ct);
+        assert_eq!(&ss[..], &record.ss[..], "decapsulated ss, count = {}", count);
+    }
+}
+
+// The response files of the NewHope round 2 submission are not in the repository
+// yet, `kat/README.md` lists them, put them into `kat/` and run
+// `cargo test kat -- --ignored`, nothing but these tests compares the crate with
+// the reference implementation.
+
+#[test]
+#[ignore = "needs kat/PQCkemKAT_896.rsp, see kat/README.md"]
+fn cpa_512() {
+    run::<Cpa<512>>("PQCkemKAT_896.rsp")
+}
+
+#[test]
+#[ignore = "needs kat/PQCkemKAT_1792.rsp, see kat/README.md"]
+fn cpa_1024() {
+    run::<Cpa<1024>>("PQCkemKAT_1792.rsp")
+}
+
+#[test]
+#[ignore = "needs kat/PQCkemKAT_1888.rsp, see kat/README.md"]
+fn cca_512() {
+    run::<Cca<512>>("PQCkemKAT_1888.rsp")
+}
+
+#[test]
+#[ignore = "needs kat/PQCkemKAT_3680.rsp, see kat/README.md"]
+fn cca_1024() {
+    run::<Cca<1024>>("PQCkemKAT_3680.rsp")
+}
+
+// the digest of the first records the generator of the response files gives,
+// the expected values are recorded from this implementation, not from the
+// response files, so they only guard it against unintended changes while the
+// files are absent, the generator itself is checked in `drbg::request_seeds`
+fn digest<K, D>() -> Vec<u8>
+where
+    K: Kem<D>,
+    D: Default + Update + ExtendableOutput,
+{
+    let mut entropy = [0; 48];
+    for (i, e) in entropy.iter_mut().enumerate() {
+        *e = i as u8;
+    }
+    let mut master = Drbg::new(&entropy);
+
+    let mut hasher = Shake256::default();
+    for _ in 0..8 {
+        master.fill(&mut entropy);
+        let mut drbg = Drbg::new(&entropy);
+
+        let (pk, sk) = K::generate_pair(&drbg.seed());
+        let pk_hash = h::<D, _, _>(&pk);
+        let (ct, ss) = K::encapsulate(&drbg.seed(), &pk, &pk_hash);
+        assert_eq!(K::decapsulate(&sk, &pk_hash, &ct), ss);
+        hasher.update(&pk.clone_line()[..]);
+        hasher.update(&sk.clone_line()[..]);
+        hasher.update(&ct.clone_line()[..]);
+        hasher.update(&ss[..]);
+    }
+
+    let mut digest = std::vec![0; 16];
+    hasher.finalize_xof().read(&mut digest);
+    digest
+}
+
+#[test]
+fn digests() {
+    assert_eq!(digest::<Cpa<512>, Shake256>(), hex("20ca947aede2e00509c4b1202aca7833"));
+    assert_eq!(digest::<Cpa<1024>, Shake256>(), hex("0ee9bdc036b39c74531ed78809fdaf2c"));
+    assert_eq!(digest::<Cca<512>, Shake256>(), hex("bf422013e9f0f2279e78b68a86b0c771"));
+    assert_eq!(digest::<Cca<1024>, Shake256>(), hex("8522d58c2f56bfd3f0a265a120d057db"));
+}
+
+// there are no response files of the 90s variant, the values are recorded
+// from this implementation and guard it against unintended changes
+#[cfg(feature = "90s")]
+#[test]
+fn digests_90s() {
+    use crate::{Aes, Sha2};
+
+    assert_eq!(digest::<Cpa<512, Aes>, Sha2>(), hex("fb1ab283efaf918d597edba04bd5e1f3"));
+    assert_eq!(digest::<Cpa<1024, Aes>, Sha2>(), hex("34b749ccf72b766475b8b30b26571618"));
+    assert_eq!(digest::<Cca<512, Aes>, Sha2>(), hex("ae075cd289361d0ddb12fb9e36a031f3"));
+    assert_eq!(digest::<Cca<1024, Aes>, Sha2>(), hex("f4c9cc2ef78046dc6611ae2b2f402492"));
+}
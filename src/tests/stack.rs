@@ -0,0 +1,74 @@
+//! Measures the peak stack of the decapsulation in the low memory mode. The stack
+//! below the current frame is painted with a pattern, then the decapsulation runs
+//! in the same place, and the deepest byte that is not the pattern anymore shows
+//! how deep it went. Meaningful only in release mode, so the test is ignored
+//! by default: `cargo test --release --features low-memory,std -- --ignored stack`,
+//! with `std` it measures both the avx2 kernels, if the cpu has them, and the
+//! portable ones.
+
+use crate::{Cca, KemExt, poly::with_portable};
+use pq_kem::Kem;
+use sha3::Shake256;
+use rac::generic_array::{GenericArray, sequence::GenericSequence};
+use core::{hint::black_box, ptr};
+
+const DEPTH: usize = 0x10000;
+const PATTERN: u8 = 0xa5;
+
+// the peak stack usage of the decapsulation of NewHope-1024 CCA
+const LIMIT: usize = 8 * 1024;
+
+// the lowest address of the painted region
+#[inline(never)]
+fn paint() -> usize {
+    let mut region = [PATTERN; DEPTH];
+    black_box(&mut region);
+    region.as_ptr() as usize
+}
+
+#[inline(never)]
+#[allow(unsafe_code)]
+fn peak<F>(f: F) -> usize
+where
+    F: FnOnce(),
+{
+    let bottom = paint();
+    f();
+    // the frame of `paint` is gone, but the memory is still the stack
+    // of this thread, nothing else writes there
+    let untouched = (0..DEPTH)
+        .take_while(|&i| unsafe { ptr::read_volatile((bottom + i) as *const u8) } == PATTERN)
+        .count();
+    DEPTH - untouched
+}
+
+#[test]
+#[ignore = "measures the stack, run in release mode with the low-memory feature"]
+fn stack_decapsulate() {
+    type K = Cca<1024>;
+
+    let (pk, sk) = <K as Kem<Shake256>>::generate_pair(&GenericArray::generate(|_| rand::random()));
+    let (ct, key) =
+        <K as KemExt<Shake256>>::encapsulate_key(&GenericArray::generate(|_| rand::random()), &pk);
+
+    let measure = || {
+        let mut key_decapsulated = GenericArray::default();
+        let used = peak(|| {
+            key_decapsulated =
+                <K as KemExt<Shake256>>::decapsulate_key(black_box(&sk), black_box(&ct))
+        });
+        assert_eq!(key_decapsulated, key);
+        used
+    };
+
+    let used = measure();
+    std::println!("decapsulation uses {} bytes of the stack", used);
+    assert!(used < LIMIT, "{} bytes", used);
+
+    let used = with_portable(measure);
+    std::println!(
+        "decapsulation uses {} bytes of the stack with the portable kernels",
+        used
+    );
+    assert!(used < LIMIT, "{} bytes with the portable kernels", used);
+}
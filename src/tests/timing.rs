@@ -0,0 +1,117 @@
+//! Dudect style leakage detection, compares the execution time on a fixed
+//! input with the execution time on random inputs by Welch's t-test.
+//! Meaningful only in release mode on a quiet machine, so the tests are
+//! ignored by default: `cargo test --release -- --ignored timing`.
+
+use crate::{Cca, Pke, Parameter, h};
+use pq_kem::Kem;
+use sha3::Shake256;
+use rac::{
+    LineValid,
+    generic_array::{GenericArray, sequence::GenericSequence},
+};
+use std::{time::Instant, vec::Vec};
+
+const SAMPLES: usize = 20_000;
+
+// dudect considers `|t| > 4.5` as the evidence of leakage
+const THRESHOLD: f64 = 4.5;
+
+/// Welch's t statistic of two classes of measurements, the measurements
+/// above the 90th percentile are cropped to cut off the system noise.
+fn welch(measurements: &[(bool, u128)]) -> f64 {
+    let mut sorted = measurements.iter().map(|&(_, t)| t).collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let crop = sorted[sorted.len() * 9 / 10];
+
+    let moments = |class: bool| {
+        let class = measurements
+            .iter()
+            .filter(|&&(c, t)| c == class && t <= crop)
+            .map(|&(_, t)| t as f64)
+            .collect::<Vec<_>>();
+        let n = class.len() as f64;
+        let mean = class.iter().sum::<f64>() / n;
+        let variance = class.iter().map(|t| (t - mean) * (t - mean)).sum::<f64>() / (n - 1.0);
+        (n, mean, variance)
+    };
+    let (n_0, mean_0, variance_0) = moments(false);
+    let (n_1, mean_1, variance_1) = moments(true);
+
+    (mean_0 - mean_1) / (variance_0 / n_0 + variance_1 / n_1).sqrt()
+}
+
+/// Prepares the inputs of both classes in advance, interleaves them randomly
+/// and measures only the call. The fixed input is copied as well, so both
+/// classes are equally cold in the cache.
+fn measure<I, F>(fixed: I, random: impl Fn() -> I, f: F) -> f64
+where
+    I: Clone,
+    F: Fn(&I),
+{
+    let inputs = (0..SAMPLES)
+        .map(|_| {
+            let class = rand::random::<bool>();
+            (class, if class { fixed.clone() } else { random() })
+        })
+        .collect::<Vec<_>>();
+
+    let measurements = inputs
+        .iter()
+        .map(|(class, input)| {
+            let start = Instant::now();
+            f(input);
+            (*class, start.elapsed().as_nanos())
+        })
+        .collect::<Vec<_>>();
+
+    welch(&measurements)
+}
+
+#[test]
+#[ignore = "statistical timing test, run in release mode"]
+fn timing_decrypt() {
+    let pke = Parameter::<1024>::new(&GenericArray::generate(|_| rand::random()));
+    let (pk_a, sk_a) = pke.generate(&GenericArray::generate(|_| rand::random()));
+    let encrypt = || {
+        pke.encrypt(
+            &GenericArray::generate(|_| rand::random()),
+            &pk_a,
+            &GenericArray::generate(|_| rand::random()),
+        )
+    };
+
+    let t = measure(encrypt(), encrypt, |(pk_b, ct)| {
+        let _ = Parameter::<1024>::decrypt(pk_b, &sk_a, ct);
+    });
+    assert!(t.abs() < THRESHOLD, "t = {}", t);
+}
+
+#[test]
+#[ignore = "statistical timing test, run in release mode"]
+fn timing_decapsulate() {
+    type K = Cca<1024>;
+
+    let (pk, sk) = <K as Kem<Shake256>>::generate_pair(&GenericArray::generate(|_| rand::random()));
+    let pk_hash = h::<Shake256, _, _>(&pk);
+    // the fixed cipher text is valid, random ones are rejected implicitly
+    let encapsulate = || {
+        let (ct, _) = <K as Kem<Shake256>>::encapsulate(
+            &GenericArray::generate(|_| rand::random()),
+            &pk,
+            &pk_hash,
+        );
+        ct
+    };
+    let reject = || {
+        let mut bytes = encapsulate().clone_line();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1 << (rand::random::<u8>() % 8);
+        LineValid::try_clone_array(&bytes).unwrap()
+    };
+
+    let t = measure(encapsulate(), reject, |ct| {
+        let _ = <K as Kem<Shake256>>::decapsulate(&sk, &pk_hash, ct);
+    });
+    assert!(t.abs() < THRESHOLD, "t = {}", t);
+}